@@ -0,0 +1,519 @@
+use indexmap::IndexMap;
+
+use crate::core::{YATWindow, layouts::{Layout, LayoutMsg, Rect, weighted_ratio}};
+
+/// Binary space partitioning layout. Every new window splits the
+/// focused one's node
+#[derive(Debug, Default)]
+pub struct Bsp {
+    root: Option<Node>,
+    presel: Option<SplitDir>, // direction of the next split only
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDir {
+    Horizontal, // children side by side
+    Vertical, // children one above another
+}
+
+impl SplitDir {
+    pub fn from_name(name: &str) -> Option<SplitDir> {
+        match name.to_lowercase().as_str() {
+            "horizontal" | "h" => Some(SplitDir::Horizontal),
+            "vertical" | "v" => Some(SplitDir::Vertical),
+            _ => None
+        }
+    }
+
+    fn toggled(&self) -> SplitDir {
+        match self {
+            SplitDir::Horizontal => SplitDir::Vertical,
+            SplitDir::Vertical => SplitDir::Horizontal,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Node {
+    Leaf(u32),
+    Split {
+        dir: SplitDir,
+        ratio: f32, // part taken by `first`
+        first: Box<Node>,
+        second: Box<Node>,
+    },
+}
+
+impl Node {
+    fn contains(&self, id: u32) -> bool {
+        match self {
+            Node::Leaf(v) => *v == id,
+            Node::Split { first, second, .. } => {
+                first.contains(id) || second.contains(id)
+            }
+        }
+    }
+
+    fn leaves(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 1,
+            Node::Split { first, second, .. } => {
+                first.leaves() + second.leaves()
+            }
+        }
+    }
+
+    fn last_leaf(&self) -> u32 {
+        match self {
+            Node::Leaf(v) => *v,
+            Node::Split { second, .. } => second.last_leaf(),
+        }
+    }
+
+    /// Replaces leaf `target` with split of it and `id`
+    fn insert(&mut self, target: u32, id: u32, presel: Option<SplitDir>,
+        depth: usize) -> bool {
+        match self {
+            Node::Leaf(v) if *v == target => {
+                // spiral-like by default
+                let dir = presel.unwrap_or(if depth.is_multiple_of(2) {
+                    SplitDir::Horizontal
+                } else {
+                    SplitDir::Vertical
+                });
+
+                *self = Node::Split {
+                    dir,
+                    ratio: 0.5,
+                    first: Box::new(Node::Leaf(target)),
+                    second: Box::new(Node::Leaf(id)),
+                };
+                true
+            }
+            Node::Leaf(_) => false,
+            Node::Split { first, second, .. } => {
+                first.insert(target, id, presel, depth + 1)
+                    || second.insert(target, id, presel, depth + 1)
+            }
+        }
+    }
+
+    /// Removes leaf `id`, its sibling takes the place of their parent.
+    /// Returns None if the node itself is removed
+    fn remove(self, id: u32) -> Option<Node> {
+        match self {
+            Node::Leaf(v) if v == id => None,
+            Node::Leaf(_) => Some(self),
+            Node::Split { dir, ratio, first, second } => {
+                match (first.remove(id), second.remove(id)) {
+                    (Some(f), Some(s)) => Some(Node::Split {
+                        dir,
+                        ratio,
+                        first: Box::new(f),
+                        second: Box::new(s),
+                    }),
+                    (Some(v), None) | (None, Some(v)) => Some(v),
+                    (None, None) => None,
+                }
+            }
+        }
+    }
+
+    /// Returns split node which is a direct parent of leaf `id`
+    fn parent_of(&mut self, id: u32) -> Option<&mut Node> {
+        let is_parent = match self {
+            Node::Leaf(_) => return None,
+            Node::Split { first, second, .. } => {
+                matches!(**first, Node::Leaf(v) if v == id)
+                    || matches!(**second, Node::Leaf(v) if v == id)
+            }
+        };
+        if is_parent {
+            return Some(self);
+        }
+
+        match self {
+            Node::Split { first, second, .. } => {
+                if first.contains(id) {
+                    first.parent_of(id)
+                } else {
+                    second.parent_of(id)
+                }
+            }
+            Node::Leaf(_) => None,
+        }
+    }
+
+    /// Swaps leaves `a` and `b`
+    fn swap(&mut self, a: u32, b: u32) {
+        match self {
+            Node::Leaf(v) if *v == a => *v = b,
+            Node::Leaf(v) if *v == b => *v = a,
+            Node::Leaf(_) => {}
+            Node::Split { first, second, .. } => {
+                first.swap(a, b);
+                second.swap(a, b);
+            }
+        }
+    }
+
+    /// Rotates subtree by 90 degrees clockwise
+    fn rotate(&mut self) {
+        if let Node::Split { dir, ratio, first, second } = self {
+            if *dir == SplitDir::Vertical {
+                std::mem::swap(first, second);
+                *ratio = 1.0 - *ratio;
+            }
+            *dir = dir.toggled();
+            first.rotate();
+            second.rotate();
+        }
+    }
+
+    /// Mirrors subtree along its split direction
+    fn flip(&mut self) {
+        if let Node::Split { ratio, first, second, .. } = self {
+            std::mem::swap(first, second);
+            *ratio = 1.0 - *ratio;
+            first.flip();
+            second.flip();
+        }
+    }
+
+    /// Gives every leaf equal area
+    fn balance(&mut self) {
+        if let Node::Split { ratio, first, second, .. } = self {
+            first.balance();
+            second.balance();
+            *ratio = first.leaves() as f32
+                / (first.leaves() + second.leaves()) as f32;
+        }
+    }
+
+    /// Places visible leaves into `area`. Subtrees without visible
+    /// windows give their space to the sibling
+    fn arrange(&self, area: Rect, windows: &IndexMap<u32, YATWindow>,
+        res: &mut Vec<(u32, Rect)>) {
+        match self {
+            Node::Leaf(v) => {
+                if windows.contains_key(v) {
+                    res.push((*v, area));
+                }
+            }
+            Node::Split { dir, ratio, first, second } => {
+                let first_vis = first.any_visible(windows);
+                let second_vis = second.any_visible(windows);
+                if !(first_vis && second_vis) {
+                    first.arrange(area, windows, res);
+                    second.arrange(area, windows, res);
+                    return;
+                }
+
+                let ratio = weighted_ratio(
+                    *ratio, 
+                    first.weight(windows, *dir), 
+                    second.weight(windows, *dir)
+                );
+                let (a, b) = match dir {
+                    SplitDir::Horizontal => {
+                        let w = (area.w as f32 * ratio) as u32;
+                        (
+                            Rect::new(area.x, area.y, w, area.h),
+                            Rect::new(area.x + w as i32, area.y,
+                                area.w - w, area.h),
+                        )
+                    }
+                    SplitDir::Vertical => {
+                        let h = (area.h as f32 * ratio) as u32;
+                        (
+                            Rect::new(area.x, area.y, area.w, h),
+                            Rect::new(area.x, area.y + h as i32,
+                                area.w, area.h - h),
+                        )
+                    }
+                };
+                first.arrange(a, windows, res);
+                second.arrange(b, windows, res);
+            }
+        }
+    }
+
+    /// Mean weight of visible leaves along `dir`
+    fn weight(&self, windows: &IndexMap<u32, YATWindow>, dir: SplitDir) 
+        -> f32 {
+        let mut ws = Vec::new();
+        self.collect_weights(windows, dir, &mut ws);
+        if ws.is_empty() {
+            return 1.0;
+        }
+        ws.iter().sum::<f32>() / ws.len() as f32
+    }
+
+    fn collect_weights(&self, windows: &IndexMap<u32, YATWindow>, 
+        dir: SplitDir, res: &mut Vec<f32>) {
+        match self {
+            Node::Leaf(v) => {
+                if let Some(w) = windows.get(v) {
+                    res.push(match dir {
+                        SplitDir::Horizontal => w.weight_w,
+                        SplitDir::Vertical => w.weight_h,
+                    });
+                }
+            }
+            Node::Split { first, second, .. } => {
+                first.collect_weights(windows, dir, res);
+                second.collect_weights(windows, dir, res);
+            }
+        }
+    }
+
+    fn any_visible(&self, windows: &IndexMap<u32, YATWindow>) -> bool {
+        match self {
+            Node::Leaf(v) => windows.contains_key(v),
+            Node::Split { first, second, .. } => {
+                first.any_visible(windows) || second.any_visible(windows)
+            }
+        }
+    }
+}
+
+impl Bsp {
+    pub fn new() -> Bsp {
+        Bsp::default()
+    }
+
+    /// Returns subtree which operations on `focused` are applied to
+    fn focused_subtree(&mut self, focused: Option<u32>) -> Option<&mut Node> {
+        let root = self.root.as_mut()?;
+        match focused {
+            Some(id) if root.contains(id) => {
+                // can't borrow root twice, so check first
+                if root.parent_of(id).is_some() {
+                    root.parent_of(id)
+                } else {
+                    Some(root)
+                }
+            }
+            _ => Some(root)
+        }
+    }
+}
+
+impl Layout for Bsp {
+    fn name(&self) -> &'static str {
+        "bsp"
+    }
+
+    fn arrange(&self, area: Rect, windows: &IndexMap<u32, YATWindow>,
+        _focused: Option<u32>) -> Vec<(u32, Rect)> {
+        let mut res = Vec::with_capacity(windows.len());
+        if let Some(root) = &self.root {
+            root.arrange(area, windows, &mut res);
+        }
+        res
+    }
+
+    fn add_wind(&mut self, id: u32, focused: Option<u32>) {
+        let presel = self.presel.take();
+        match &mut self.root {
+            None => self.root = Some(Node::Leaf(id)),
+            Some(root) => {
+                let target = focused
+                    .filter(|f| root.contains(*f))
+                    .unwrap_or_else(|| root.last_leaf());
+                root.insert(target, id, presel, 0);
+            }
+        }
+    }
+
+    fn rm_wind(&mut self, id: u32) {
+        self.root = self.root.take().and_then(|r| r.remove(id));
+    }
+
+    fn message(&mut self, msg: &LayoutMsg, focused: Option<u32>) {
+        match msg {
+            LayoutMsg::SetSplit(dir) => self.presel = *dir,
+            LayoutMsg::Rotate => {
+                if let Some(n) = self.focused_subtree(focused) {
+                    n.rotate();
+                }
+            }
+            LayoutMsg::Flip => {
+                if let Some(n) = self.focused_subtree(focused) {
+                    n.flip();
+                }
+            }
+            LayoutMsg::Balance => {
+                if let Some(root) = self.root.as_mut() {
+                    root.balance();
+                }
+            }
+            LayoutMsg::Swap(a, b) => {
+                if let Some(root) = self.root.as_mut() {
+                    root.swap(*a, *b);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tree as string, e.g. `H(1,V(2,3))`
+    fn shape(node: &Node) -> String {
+        match node {
+            Node::Leaf(v) => v.to_string(),
+            Node::Split { dir, first, second, .. } => format!(
+                "{}({},{})",
+                if *dir == SplitDir::Horizontal { "H" } else { "V" },
+                shape(first),
+                shape(second)
+            ),
+        }
+    }
+
+    fn ratio(node: &Node) -> f32 {
+        match node {
+            Node::Split { ratio, .. } => *ratio,
+            Node::Leaf(_) => panic!("leaf has no ratio"),
+        }
+    }
+
+    fn windows(ids: &[u32]) -> IndexMap<u32, YATWindow> {
+        ids.iter().map(|id| (*id, YATWindow::new(*id, 0, 0))).collect()
+    }
+
+    /// 1 | 2 above 3, each added while the previous one is focused
+    fn three() -> Bsp {
+        let mut bsp = Bsp::new();
+        bsp.add_wind(1, None);
+        bsp.add_wind(2, Some(1));
+        bsp.add_wind(3, Some(2));
+        bsp
+    }
+
+    fn tree(bsp: &Bsp) -> String {
+        bsp.root.as_ref().map(shape).unwrap_or_default()
+    }
+
+    #[test]
+    fn insert_spirals() {
+        let bsp = three();
+        assert_eq!(tree(&bsp), "H(1,V(2,3))");
+
+        let area = Rect::new(0, 0, 100, 100);
+        assert_eq!(bsp.arrange(area, &windows(&[1, 2, 3]), None), vec![
+            (1, Rect::new(0, 0, 50, 100)),
+            (2, Rect::new(50, 0, 50, 50)),
+            (3, Rect::new(50, 50, 50, 50)),
+        ]);
+    }
+
+    #[test]
+    fn insert_presel_and_unfocused() {
+        let mut bsp = Bsp::new();
+        bsp.add_wind(1, None);
+        bsp.message(&LayoutMsg::SetSplit(Some(SplitDir::Vertical)), None);
+        bsp.add_wind(2, Some(1));
+        assert_eq!(tree(&bsp), "V(1,2)");
+
+        // presel is used once, unknown focus splits the last leaf
+        bsp.add_wind(3, Some(42));
+        assert_eq!(tree(&bsp), "V(1,V(2,3))");
+    }
+
+    #[test]
+    fn remove_promotes_sibling() {
+        let mut bsp = three();
+        bsp.rm_wind(2);
+        assert_eq!(tree(&bsp), "H(1,3)");
+        bsp.rm_wind(1);
+        assert_eq!(tree(&bsp), "3");
+        bsp.rm_wind(3);
+        assert!(bsp.root.is_none());
+    }
+
+    #[test]
+    fn parent_of_leaf() {
+        let mut bsp = three();
+        let root = bsp.root.as_mut().unwrap();
+        assert_eq!(shape(root.parent_of(3).unwrap()), "V(2,3)");
+        assert_eq!(shape(root.parent_of(1).unwrap()), "H(1,V(2,3))");
+        assert!(root.parent_of(42).is_none());
+        assert!(Node::Leaf(1).parent_of(1).is_none());
+    }
+
+    #[test]
+    fn rotate_clockwise() {
+        let mut bsp = three();
+        bsp.message(&LayoutMsg::Rotate, Some(1));
+        assert_eq!(tree(&bsp), "V(1,H(3,2))");
+
+        // focused subtree only
+        let mut bsp = three();
+        bsp.message(&LayoutMsg::Rotate, Some(3));
+        assert_eq!(tree(&bsp), "H(1,H(3,2))");
+    }
+
+    #[test]
+    fn flip_mirrors() {
+        let mut bsp = three();
+        bsp.root.as_mut().unwrap().flip();
+        assert_eq!(tree(&bsp), "H(V(3,2),1)");
+
+        let mut bsp = three();
+        bsp.message(&LayoutMsg::Flip, Some(2));
+        assert_eq!(tree(&bsp), "H(1,V(3,2))");
+    }
+
+    #[test]
+    fn balance_equal_areas() {
+        let mut bsp = three();
+        bsp.message(&LayoutMsg::Balance, None);
+        let root = bsp.root.as_mut().unwrap();
+        assert!((ratio(root) - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(ratio(root.parent_of(2).unwrap()), 0.5);
+
+        let area = Rect::new(0, 0, 90, 90);
+        assert_eq!(bsp.arrange(area, &windows(&[1, 2, 3]), None), vec![
+            (1, Rect::new(0, 0, 30, 90)),
+            (2, Rect::new(30, 0, 60, 45)),
+            (3, Rect::new(30, 45, 60, 45)),
+        ]);
+    }
+
+    #[test]
+    fn swap_leaves() {
+        let mut bsp = three();
+        bsp.message(&LayoutMsg::Swap(1, 3), None);
+        assert_eq!(tree(&bsp), "H(3,V(2,1))");
+    }
+
+    #[test]
+    fn weighted_arrange() {
+        let mut bsp = Bsp::new();
+        bsp.add_wind(1, None);
+        bsp.add_wind(2, Some(1));
+
+        let mut winds = windows(&[1, 2]);
+        winds.get_mut(&1).unwrap().weight_w = 1.5;
+        let area = Rect::new(10, 0, 100, 50);
+        assert_eq!(bsp.arrange(area, &winds, None), vec![
+            (1, Rect::new(10, 0, 60, 50)),
+            (2, Rect::new(70, 0, 40, 50)),
+        ]);
+    }
+
+    #[test]
+    fn hidden_gives_space_to_sibling() {
+        let bsp = three();
+        let area = Rect::new(0, 0, 100, 100);
+        assert_eq!(bsp.arrange(area, &windows(&[1, 3]), None), vec![
+            (1, Rect::new(0, 0, 50, 100)),
+            (3, Rect::new(50, 0, 50, 100)),
+        ]);
+    }
+}
@@ -6,7 +6,7 @@ use serde::Deserialize;
 
 #[macro_use]
 use maplit;
-use x11rb::{connection::Connection, protocol::xproto::{ConnectionExt, Keycode}};
+use x11rb::{connection::Connection, protocol::xproto::{ConnectionExt, Keycode, ModMask}};
 use xkb::Keysym;
 
 use crate::core::{self, CustomError}; 
@@ -64,6 +64,21 @@ impl Config {
             focus_new: Some(true),
             def_wrksp_ctr: None,
             autostart: None,
+            def_layout: None,
+            wrksp_layouts: None,
+            master_ratio: None,
+            master_count: None,
+            gaps_inner: None,
+            gaps_outer: None,
+            smart_gaps: None,
+            wrksp_gaps: None,
+            border_width: None,
+            border_focused: None,
+            border_unfocused: None,
+            border_urgent: None,
+            drag_tiled: None,
+            focus_steal: None,
+            size_hints_tiled: None,
         }
     }
 }  
@@ -102,13 +117,100 @@ pub fn keycode_to_keysym<C: Connection>(conn: &C, code: u8)
     Some(xkb::Keysym(keysym))
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct General {
     pub mainmod: String, // main modifier key
     pub sh: Option<String>,
     pub focus_new: Option<bool>,
     pub def_wrksp_ctr: Option<usize>,
     pub autostart: Option<Vec<String>>,
+    pub def_layout: Option<String>, // columns/rows/master/grid/monocle/bsp
+    pub wrksp_layouts: Option<HashMap<usize, String>>, // workspace idx -> layout
+    pub master_ratio: Option<f32>, // 0.1..0.9
+    pub master_count: Option<usize>,
+    pub gaps_inner: Option<u32>, // between windows, px
+    pub gaps_outer: Option<u32>, // between windows and screen edges, px
+    pub smart_gaps: Option<bool>, // no gaps with only one window
+    pub wrksp_gaps: Option<HashMap<usize, GapsCfg>>, // workspace idx -> gaps
+    pub border_width: Option<u32>, // px
+    pub border_focused: Option<String>, // "#rrggbb"
+    pub border_unfocused: Option<String>,
+    pub border_urgent: Option<String>,
+    pub drag_tiled: Option<DragTiled>, // what dragging tiled window does
+    pub focus_steal: Option<FocusSteal>, // activation requests from apps
+    pub size_hints_tiled: Option<bool>, // apply WM_NORMAL_HINTS to tiled 
+                                        // windows too
+}
+
+impl General {
+    pub fn mainmod_mask(&self) -> Option<ModMask> {
+        match self.mainmod.to_lowercase().as_str() {
+            "super" | "win" => Some(ModMask::M4),
+            "alt" => Some(ModMask::M1),
+            "shift" => Some(ModMask::SHIFT),
+            "ctrl" | "control" => Some(ModMask::CONTROL),
+            _ => None
+        }
+    }
+
+    /// Whether dragged tiled windows are swapped with the one under 
+    /// pointer instead of becoming floating
+    pub fn drag_swaps(&self) -> bool {
+        self.drag_tiled == Some(DragTiled::Swap)
+    }
+
+    pub fn border_focused(&self) -> u32 {
+        parse_color(self.border_focused.as_deref(), 0x5294e2)
+    }
+
+    pub fn border_unfocused(&self) -> u32 {
+        parse_color(self.border_unfocused.as_deref(), 0x2f343f)
+    }
+
+    pub fn border_urgent(&self) -> u32 {
+        parse_color(self.border_urgent.as_deref(), 0xe0524b)
+    }
+}
+
+/// Parses "#rrggbb" color into pixel value, `def` on failure
+fn parse_color(s: Option<&str>, def: u32) -> u32 {
+    let Some(s) = s else {
+        return def;
+    };
+    
+    match u32::from_str_radix(s.trim_start_matches('#'), 16) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Invalid color {}: {}", s, e);
+            def
+        }
+    }
+}
+
+/// What happens to tiled window dragged by mouse
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DragTiled {
+    #[default]
+    Float, // it becomes floating
+    Swap, // it's swapped with the one under pointer
+}
+
+/// What to do when an app asks to activate its window
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusSteal {
+    #[default]
+    Allow,
+    Urgent, // only mark it urgent
+    Deny,
+}
+
+/// Per-workspace gaps override
+#[derive(Debug, Deserialize, Clone)]
+pub struct GapsCfg {
+    pub inner: Option<u32>,
+    pub outer: Option<u32>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -122,6 +224,24 @@ pub enum ActionEnum {
     CfgReload(usize),
     FocusOther(isize),
     ExpandMacro(String, ActionValue), // macro name
+    SetLayout(String), // layout name for cur workspace
+    DeltaMasterRatio(isize), // in percents
+    DeltaMasterCount(isize),
+    CycleLayout(isize),
+    SetSplitDir(String), // horizontal/vertical/auto, for the next split
+    RotateSplit,
+    FlipSplit,
+    BalanceTree,
+    ResizeFocused { dx: isize, dy: isize }, // size weights delta in percents
+    BalanceWorkspace,
+    DeltaGapsInner(isize), // px
+    DeltaGapsOuter(isize),
+    ToggleGaps,
+    ToggleFloating,
+    ToggleFullscreen,
+    CloseFocused, // asks focused window to close
+    KillFocused, // kills client of focused window
+    FocusUrgent, // switches to the oldest urgent window
 }
 
 impl ActionEnum {
@@ -143,6 +263,27 @@ impl ActionEnum {
             (ActionEnum::SwitchWorkspace(_), ActionValue::Uint(u)) => {
                 Ok(ActionEnum::SwitchWorkspace(u))
             }
+            (ActionEnum::SetLayout(_), ActionValue::Str(s)) => {
+                Ok(ActionEnum::SetLayout(s))
+            }
+            (ActionEnum::DeltaMasterRatio(_), ActionValue::Int(i)) => {
+                Ok(ActionEnum::DeltaMasterRatio(i))
+            }
+            (ActionEnum::DeltaMasterCount(_), ActionValue::Int(i)) => {
+                Ok(ActionEnum::DeltaMasterCount(i))
+            }
+            (ActionEnum::CycleLayout(_), ActionValue::Int(i)) => {
+                Ok(ActionEnum::CycleLayout(i))
+            }
+            (ActionEnum::SetSplitDir(_), ActionValue::Str(s)) => {
+                Ok(ActionEnum::SetSplitDir(s))
+            }
+            (ActionEnum::DeltaGapsInner(_), ActionValue::Int(i)) => {
+                Ok(ActionEnum::DeltaGapsInner(i))
+            }
+            (ActionEnum::DeltaGapsOuter(_), ActionValue::Int(i)) => {
+                Ok(ActionEnum::DeltaGapsOuter(i))
+            }
             other => Err(Box::new(CustomError {
                 message: format!("{:?} couldn't be replaced with {:?}",
                              other.0, other.1)
@@ -0,0 +1,305 @@
+use x11rb::{COPY_DEPTH_FROM_PARENT, connection::Connection, errors::{ReplyError, ReplyOrIdError}, protocol::xproto::{AtomEnum, ConnectionExt, CreateWindowAux, PropMode, Screen, WindowClass}, wrapper::ConnectionExt as _};
+
+use crate::core::{atoms::Atoms, layouts::Rect};
+
+const WM_NAME: &str = "yatwm";
+
+/// Hints from EWMH spec that yatwm supports, published in _NET_SUPPORTED
+fn supported(atoms: &Atoms) -> Vec<u32> {
+    vec![
+        atoms._NET_SUPPORTED,
+        atoms._NET_SUPPORTING_WM_CHECK,
+        atoms._NET_CLIENT_LIST,
+        atoms._NET_CLIENT_LIST_STACKING,
+        atoms._NET_NUMBER_OF_DESKTOPS,
+        atoms._NET_CURRENT_DESKTOP,
+        atoms._NET_DESKTOP_NAMES,
+        atoms._NET_DESKTOP_VIEWPORT,
+        atoms._NET_WM_DESKTOP,
+        atoms._NET_ACTIVE_WINDOW,
+        atoms._NET_CLOSE_WINDOW,
+        atoms._NET_WM_NAME,
+        atoms._NET_WM_STATE,
+        atoms._NET_WM_STATE_FULLSCREEN,
+        atoms._NET_WM_STATE_DEMANDS_ATTENTION,
+        atoms._NET_WORKAREA,
+        atoms._NET_WM_STRUT,
+        atoms._NET_WM_STRUT_PARTIAL,
+        atoms._NET_WM_WINDOW_TYPE,
+        atoms._NET_WM_WINDOW_TYPE_NORMAL,
+        atoms._NET_WM_WINDOW_TYPE_DOCK,
+        atoms._NET_WM_WINDOW_TYPE_DIALOG,
+        atoms._NET_WM_WINDOW_TYPE_UTILITY,
+        atoms._NET_WM_WINDOW_TYPE_SPLASH,
+        atoms._NET_WM_WINDOW_TYPE_TOOLBAR,
+        atoms._NET_WM_WINDOW_TYPE_NOTIFICATION,
+        atoms._NET_WM_WINDOW_TYPE_TOOLTIP,
+    ]
+}
+
+/// How window is handled on map, based on _NET_WM_WINDOW_TYPE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowKind {
+    Normal, // tiled
+    Dock, // unmanaged, reserves space
+    Floating, // dialogs, splash screens etc., centered
+    Unmanaged, // notifications and tooltips, mapped as-is
+}
+
+/// Creates supporting WM check window and publishes root properties 
+/// telling clients that EWMH-compliant WM is running. 
+/// Returns check window id
+pub fn init<C: Connection>(conn: &C, screen: &Screen, atoms: &Atoms) 
+    -> Result<u32, ReplyOrIdError> {
+    let check = conn.generate_id()?;
+    conn.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        check,
+        screen.root,
+        -1,
+        -1,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_ONLY,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new().override_redirect(1)
+    )?;
+
+    // both root and check window point to check window 
+    for wind in [screen.root, check] {
+        conn.change_property32(
+            PropMode::REPLACE,
+            wind,
+            atoms._NET_SUPPORTING_WM_CHECK,
+            AtomEnum::WINDOW,
+            &[check]
+        )?;
+    }
+    conn.change_property8(
+        PropMode::REPLACE,
+        check,
+        atoms._NET_WM_NAME,
+        atoms.UTF8_STRING,
+        WM_NAME.as_bytes()
+    )?;
+
+    conn.change_property32(
+        PropMode::REPLACE,
+        screen.root,
+        atoms._NET_SUPPORTED,
+        AtomEnum::ATOM,
+        &supported(atoms)
+    )?;
+
+    Ok(check)
+}
+
+/// Publishes managed windows in mapping (`clients`) and 
+/// stacking (`stacking`, bottom to top) order
+pub fn set_client_list<C: Connection>(conn: &C, atoms: &Atoms, root: u32,
+    clients: &[u32], stacking: &[u32]) -> Result<(), ReplyOrIdError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_CLIENT_LIST,
+        AtomEnum::WINDOW,
+        clients
+    )?;
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_CLIENT_LIST_STACKING,
+        AtomEnum::WINDOW,
+        stacking
+    )?;
+    Ok(())
+}
+
+/// Publishes desktops: their count, names and the current one. 
+/// Desktops are 0-based while workspaces are 1-based
+pub fn set_desktops<C: Connection>(conn: &C, atoms: &Atoms, root: u32,
+    names: &[String], current: u32) -> Result<(), ReplyOrIdError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_NUMBER_OF_DESKTOPS,
+        AtomEnum::CARDINAL,
+        &[names.len() as u32]
+    )?;
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_CURRENT_DESKTOP,
+        AtomEnum::CARDINAL,
+        &[current]
+    )?;
+
+    // null-separated list
+    let names_prop: Vec<u8> = names.iter()
+        .flat_map(|n| n.bytes().chain(std::iter::once(0)))
+        .collect();
+    conn.change_property8(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_DESKTOP_NAMES,
+        atoms.UTF8_STRING,
+        &names_prop
+    )?;
+
+    // no large desktops, every viewport is at 0,0
+    let viewports = vec![0; names.len() * 2];
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_DESKTOP_VIEWPORT,
+        AtomEnum::CARDINAL,
+        &viewports
+    )?;
+    Ok(())
+}
+
+/// Sets _NET_WM_DESKTOP of window `wind`
+pub fn set_wm_desktop<C: Connection>(conn: &C, atoms: &Atoms, wind: u32, 
+    desktop: u32) -> Result<(), ReplyOrIdError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        wind,
+        atoms._NET_WM_DESKTOP,
+        AtomEnum::CARDINAL,
+        &[desktop]
+    )?;
+    Ok(())
+}
+
+/// Sets _NET_WORKAREA of every of `desktops` desktops to `area`
+pub fn set_workarea<C: Connection>(conn: &C, atoms: &Atoms, root: u32,
+    area: Rect, desktops: usize) -> Result<(), ReplyOrIdError> {
+    let geom = [area.x as u32, area.y as u32, area.w, area.h];
+    let prop: Vec<u32> = geom.iter().copied().cycle()
+        .take(geom.len() * desktops)
+        .collect();
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_WORKAREA,
+        AtomEnum::CARDINAL,
+        &prop
+    )?;
+    Ok(())
+}
+
+/// Sets _NET_ACTIVE_WINDOW to `wind`, NONE if nothing is focused
+pub fn set_active_window<C: Connection>(conn: &C, atoms: &Atoms, root: u32,
+    wind: u32) -> Result<(), ReplyOrIdError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        root,
+        atoms._NET_ACTIVE_WINDOW,
+        AtomEnum::WINDOW,
+        &[wind]
+    )?;
+    Ok(())
+}
+
+/// Reads _NET_WM_DESKTOP of window `wind`
+pub fn get_wm_desktop<C: Connection>(conn: &C, atoms: &Atoms, wind: u32) 
+    -> Result<Option<u32>, ReplyError> {
+    let reply = conn.get_property(
+        false,
+        wind,
+        atoms._NET_WM_DESKTOP,
+        AtomEnum::CARDINAL,
+        0,
+        1
+    )?.reply()?;
+    Ok(reply.value32().and_then(|mut v| v.next()))
+}
+
+/// Adds or removes `states` atoms in _NET_WM_STATE of window `wind`,
+/// keeping states set by clients and other tools
+pub fn set_wm_state<C: Connection>(conn: &C, atoms: &Atoms, wind: u32, 
+    states: &[(u32, bool)]) -> Result<(), ReplyOrIdError> {
+    let mut state = get_atoms(conn, wind, atoms._NET_WM_STATE)?;
+    state.retain(|s| !states.iter().any(|(a, _)| a == s));
+    state.extend(states.iter().filter(|(_, on)| *on).map(|(a, _)| *a));
+
+    conn.change_property32(
+        PropMode::REPLACE,
+        wind,
+        atoms._NET_WM_STATE,
+        AtomEnum::ATOM,
+        &state
+    )?;
+    Ok(())
+}
+
+/// Returns atoms stored in property `prop` of window `wind`, 
+/// empty if it's unset
+pub fn get_atoms<C: Connection>(conn: &C, wind: u32, prop: u32) 
+    -> Result<Vec<u32>, ReplyError> {
+    let reply = conn.get_property(
+        false,
+        wind,
+        prop,
+        AtomEnum::ATOM,
+        0,
+        u32::MAX
+    )?.reply()?;
+    Ok(reply.value32().map(|v| v.collect()).unwrap_or_default())
+}
+
+/// Reads _NET_WM_WINDOW_TYPE of window `wind`. The first known type
+/// wins as the list is in order of preference
+pub fn get_window_kind<C: Connection>(conn: &C, atoms: &Atoms, wind: u32) 
+    -> Result<WindowKind, ReplyError> {
+    let types = get_atoms(conn, wind, atoms._NET_WM_WINDOW_TYPE)?;
+    for t in types {
+        if t == atoms._NET_WM_WINDOW_TYPE_NORMAL {
+            return Ok(WindowKind::Normal);
+        } else if t == atoms._NET_WM_WINDOW_TYPE_DOCK {
+            return Ok(WindowKind::Dock);
+        } else if t == atoms._NET_WM_WINDOW_TYPE_DIALOG
+            || t == atoms._NET_WM_WINDOW_TYPE_UTILITY
+            || t == atoms._NET_WM_WINDOW_TYPE_SPLASH
+            || t == atoms._NET_WM_WINDOW_TYPE_TOOLBAR {
+            return Ok(WindowKind::Floating);
+        } else if t == atoms._NET_WM_WINDOW_TYPE_NOTIFICATION
+            || t == atoms._NET_WM_WINDOW_TYPE_TOOLTIP {
+            return Ok(WindowKind::Unmanaged);
+        }
+    }
+    Ok(WindowKind::Normal)
+}
+
+/// Reads space reserved by window `wind` at left, right, top and bottom 
+/// edges. _NET_WM_STRUT_PARTIAL is preferred, only its first four 
+/// values are used
+pub fn get_strut<C: Connection>(conn: &C, atoms: &Atoms, wind: u32) 
+    -> Result<Option<[u32; 4]>, ReplyError> {
+    for prop in [atoms._NET_WM_STRUT_PARTIAL, atoms._NET_WM_STRUT] {
+        let reply = conn.get_property(
+            false,
+            wind,
+            prop,
+            AtomEnum::CARDINAL,
+            0,
+            12
+        )?.reply()?;
+        let vals: Vec<u32> = reply.value32()
+            .map(|v| v.collect())
+            .unwrap_or_default();
+        if vals.len() >= 4 {
+            return Ok(Some([vals[0], vals[1], vals[2], vals[3]]));
+        }
+    }
+    Ok(None)
+}
+
+/// Removes _NET_WM_DESKTOP and _NET_WM_STATE of withdrawn window `wind`
+pub fn clear_wind<C: Connection>(conn: &C, atoms: &Atoms, wind: u32) 
+    -> Result<(), ReplyOrIdError> {
+    conn.delete_property(wind, atoms._NET_WM_DESKTOP)?;
+    conn.delete_property(wind, atoms._NET_WM_STATE)?;
+    Ok(())
+}
@@ -0,0 +1,330 @@
+use std::{thread, time::Duration};
+
+use x11rb::{COPY_DEPTH_FROM_PARENT, connection::Connection, errors::{ConnectionError, ReplyError}, properties::{WmHints, WmSizeHints}, protocol::{Event, xproto::{AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConnectionExt, CreateWindowAux, EventMask, PropMode, Screen, WindowClass}}, wrapper::ConnectionExt as _};
+
+use crate::core::{CustomError, atoms::Atoms};
+
+// WM_STATE values
+pub const WM_STATE_WITHDRAWN: u32 = 0;
+pub const WM_STATE_NORMAL: u32 = 1;
+pub const WM_STATE_ICONIC: u32 = 3;
+
+// how long to wait for the replaced WM to exit
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
+const REPLACE_POLL: Duration = Duration::from_millis(50);
+
+/// Returns protocols listed in WM_PROTOCOLS of window `wind`
+pub fn get_protocols<C: Connection>(conn: &C, atoms: &Atoms, wind: u32) 
+    -> Result<Vec<u32>, ReplyError> {
+    let reply = conn.get_property(
+        false,
+        wind,
+        atoms.WM_PROTOCOLS,
+        AtomEnum::ATOM,
+        0,
+        u32::MAX
+    )?.reply()?;
+    Ok(reply.value32().map(|v| v.collect()).unwrap_or_default())
+}
+
+/// Sets WM_STATE of window `wind` to `state`, without icon window
+pub fn set_wm_state<C: Connection>(conn: &C, atoms: &Atoms, wind: u32, 
+    state: u32) -> Result<(), ConnectionError> {
+    conn.change_property32(
+        PropMode::REPLACE,
+        wind,
+        atoms.WM_STATE,
+        atoms.WM_STATE,
+        &[state, x11rb::NONE]
+    )?;
+    Ok(())
+}
+
+/// Reads state field of WM_STATE of window `wind`
+pub fn get_wm_state<C: Connection>(conn: &C, atoms: &Atoms, wind: u32) 
+    -> Result<Option<u32>, ReplyError> {
+    let reply = conn.get_property(
+        false,
+        wind,
+        atoms.WM_STATE,
+        atoms.WM_STATE,
+        0,
+        2
+    )?.reply()?;
+    Ok(reply.value32().and_then(|mut v| v.next()))
+}
+
+/// Reads WM_HINTS of window `wind`
+pub fn get_wm_hints<C: Connection>(conn: &C, wind: u32) 
+    -> Result<Option<WmHints>, ReplyError> {
+    WmHints::get(conn, wind)?.reply()
+}
+
+/// Whether window with `hints` accepts input focus from WM. 
+/// Windows without WM_HINTS are assumed to accept it
+pub fn accepts_input(hints: Option<&WmHints>) -> bool {
+    hints.and_then(|h| h.input).unwrap_or(true)
+}
+
+/// Sends WM_PROTOCOLS message `protocol` (e.g. WM_DELETE_WINDOW) 
+/// to window `wind`
+pub fn send_protocol<C: Connection>(conn: &C, atoms: &Atoms, wind: u32, 
+    protocol: u32, time: u32) -> Result<(), ConnectionError> {
+    let event = ClientMessageEvent::new(
+        32,
+        wind,
+        atoms.WM_PROTOCOLS,
+        [protocol, time, 0, 0, 0]
+    );
+    conn.send_event(false, wind, EventMask::NO_EVENT, event)?;
+    Ok(())
+}
+
+/// Returns window that window `wind` is transient for (e.g. parent of 
+/// a dialog)
+pub fn get_transient_for<C: Connection>(conn: &C, wind: u32) 
+    -> Result<Option<u32>, ReplyError> {
+    let reply = conn.get_property(
+        false,
+        wind,
+        AtomEnum::WM_TRANSIENT_FOR,
+        AtomEnum::WINDOW,
+        0,
+        1
+    )?.reply()?;
+    Ok(reply.value32().and_then(|mut v| v.next()).filter(|w| *w != 0))
+}
+
+/// Reads WM_NORMAL_HINTS of window `wind`
+pub fn get_size_hints<C: Connection>(conn: &C, wind: u32) 
+    -> Result<Option<WmSizeHints>, ReplyError> {
+    WmSizeHints::get_normal_hints(conn, wind)?.reply()
+}
+
+/// Whether window can't be resized, i.e. its min size equals max size
+pub fn is_fixed(hints: &WmSizeHints) -> bool {
+    matches!((hints.min_size, hints.max_size), 
+        (Some(min), Some(max)) if min == max && min.0 > 0 && min.1 > 0)
+}
+
+/// Returns the closest size to `w`x`h` that satisfies `hints`
+pub fn constrain(hints: &WmSizeHints, w: u32, h: u32) -> (u32, u32) {
+    // base and min size default to each other
+    let base = hints.base_size.or(hints.min_size).unwrap_or((0, 0));
+    let min = hints.min_size.or(hints.base_size).unwrap_or((1, 1));
+    let (mut w, mut h) = (w as i32 - base.0, h as i32 - base.1);
+
+    if let Some((amin, amax)) = hints.aspect 
+        && w > 0 && h > 0 && amin.denominator > 0 && amax.denominator > 0 {
+        let ratio = w as f32 / h as f32;
+        let min_ratio = amin.numerator as f32 / amin.denominator as f32;
+        let max_ratio = amax.numerator as f32 / amax.denominator as f32;
+        if max_ratio > 0.0 && ratio > max_ratio {
+            w = (h as f32 * max_ratio) as i32;
+        } else if min_ratio > 0.0 && ratio < min_ratio {
+            h = (w as f32 / min_ratio) as i32;
+        }
+    }
+
+    if let Some((inc_w, inc_h)) = hints.size_increment {
+        if inc_w > 0 {
+            w -= w.rem_euclid(inc_w);
+        }
+        if inc_h > 0 {
+            h -= h.rem_euclid(inc_h);
+        }
+    }
+
+    let (mut w, mut h) = ((w + base.0).max(min.0), (h + base.1).max(min.1));
+    if let Some((max_w, max_h)) = hints.max_size {
+        if max_w > 0 {
+            w = w.min(max_w);
+        }
+        if max_h > 0 {
+            h = h.min(max_h);
+        }
+    }
+    (w.max(1) as u32, h.max(1) as u32)
+}
+
+/// Takes WM_S<n> manager selection of screen `scr_num`. If another WM 
+/// owns it, waits for that WM to exit when `replace` is set, fails 
+/// otherwise. Returns window owning the selection, server time it was 
+/// taken at and events received meanwhile, which should be handled later
+pub fn acquire_wm_selection<C: Connection>(conn: &C, atoms: &Atoms, 
+    screen: &Screen, scr_num: usize, replace: bool) 
+    -> Result<(u32, u32, Vec<Event>), Box<dyn std::error::Error>> {
+    let name = format!("WM_S{}", scr_num);
+    let sel = conn.intern_atom(false, name.as_bytes())?.reply()?.atom;
+
+    let old = conn.get_selection_owner(sel)?.reply()?.owner;
+    if old != x11rb::NONE {
+        if !replace {
+            return Err(Box::new(CustomError {
+                message: "another window manager is already running, \
+                    use --replace to replace it".to_owned()
+            }));
+        }
+        // old WM destroys its selection window on exit
+        conn.change_window_attributes(
+            old,
+            &ChangeWindowAttributesAux::new()
+                .event_mask(EventMask::STRUCTURE_NOTIFY)
+        )?;
+    }
+
+    let owner = conn.generate_id()?;
+    conn.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        owner,
+        screen.root,
+        -1, -1, 1, 1, 0,
+        WindowClass::INPUT_ONLY,
+        x11rb::COPY_FROM_PARENT,
+        &CreateWindowAux::new()
+            .override_redirect(1)
+            .event_mask(EventMask::PROPERTY_CHANGE)
+    )?;
+
+    // selection needs real server time, zero-length append to 
+    // a property gets it without changing anything
+    let mut pending = Vec::new();
+    conn.change_property8(
+        PropMode::APPEND, 
+        owner, 
+        AtomEnum::WM_NAME, 
+        AtomEnum::STRING, 
+        &[]
+    )?;
+    let time = match wait_event(conn, &mut pending, |ev| {
+        matches!(ev, Event::PropertyNotify(e) if e.window == owner)
+    })? {
+        Some(Event::PropertyNotify(e)) => e.time,
+        _ => return Err(Box::new(CustomError {
+            message: "failed to get server time".to_owned()
+        })),
+    };
+
+    conn.set_selection_owner(owner, sel, time)?;
+    if conn.get_selection_owner(sel)?.reply()?.owner != owner {
+        return Err(Box::new(CustomError {
+            message: format!("failed to acquire {} selection", name)
+        }));
+    }
+
+    if old != x11rb::NONE && wait_event(conn, &mut pending, |ev| {
+        matches!(ev, Event::DestroyNotify(e) if e.window == old)
+    })?.is_none() {
+        return Err(Box::new(CustomError {
+            message: "old window manager didn't exit in time".to_owned()
+        }));
+    }
+
+    // tells clients that new WM is running
+    let event = ClientMessageEvent::new(
+        32,
+        screen.root,
+        atoms.MANAGER,
+        [time, sel, owner, 0, 0]
+    );
+    conn.send_event(false, screen.root, EventMask::STRUCTURE_NOTIFY, event)?;
+    conn.flush()?;
+    Ok((owner, time, pending))
+}
+
+/// Waits up to REPLACE_TIMEOUT for event matching `pred` and returns it.
+/// Other events are put to `pending`
+fn wait_event<C: Connection>(conn: &C, pending: &mut Vec<Event>, 
+    pred: impl Fn(&Event) -> bool) 
+    -> Result<Option<Event>, Box<dyn std::error::Error>> {
+    conn.flush()?;
+    let mut waited = Duration::ZERO;
+    while waited < REPLACE_TIMEOUT {
+        while let Some(ev) = conn.poll_for_event()? {
+            if pred(&ev) {
+                return Ok(Some(ev));
+            }
+            pending.push(ev);
+        }
+        thread::sleep(REPLACE_POLL);
+        waited += REPLACE_POLL;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::properties::AspectRatio;
+
+    use super::*;
+
+    fn hints(min: Option<(i32, i32)>, max: Option<(i32, i32)>) 
+        -> WmSizeHints {
+        let mut res = WmSizeHints::new();
+        res.min_size = min;
+        res.max_size = max;
+        res
+    }
+
+    #[test]
+    fn constrain_no_hints() {
+        let h = WmSizeHints::new();
+        assert_eq!(constrain(&h, 300, 200), (300, 200));
+        assert_eq!(constrain(&h, 0, 0), (1, 1));
+    }
+
+    #[test]
+    fn constrain_min_max() {
+        let h = hints(Some((100, 50)), Some((400, 300)));
+        assert_eq!(constrain(&h, 50, 20), (100, 50));
+        assert_eq!(constrain(&h, 500, 500), (400, 300));
+        assert_eq!(constrain(&h, 200, 100), (200, 100));
+
+        // zero max means no limit
+        let h = hints(None, Some((0, 300)));
+        assert_eq!(constrain(&h, 5000, 5000), (5000, 300));
+    }
+
+    #[test]
+    fn constrain_increments() {
+        let mut h = WmSizeHints::new();
+        h.size_increment = Some((10, 10));
+        assert_eq!(constrain(&h, 57, 33), (50, 30));
+
+        // counted from base size
+        h.base_size = Some((10, 20));
+        h.size_increment = Some((7, 9));
+        assert_eq!(constrain(&h, 100, 100), (94, 92));
+
+        // min size is the base if there's none
+        let mut h = hints(Some((5, 5)), None);
+        h.size_increment = Some((10, 10));
+        assert_eq!(constrain(&h, 57, 33), (55, 25));
+        assert_eq!(constrain(&h, 1, 1), (5, 5));
+    }
+
+    #[test]
+    fn constrain_aspect() {
+        let mut h = WmSizeHints::new();
+        h.aspect = Some((AspectRatio::new(1, 1), AspectRatio::new(2, 1)));
+        assert_eq!(constrain(&h, 400, 100), (200, 100));
+        assert_eq!(constrain(&h, 100, 200), (100, 100));
+        assert_eq!(constrain(&h, 150, 100), (150, 100));
+
+        // broken ratios are ignored
+        h.aspect = Some((AspectRatio::new(1, 0), AspectRatio::new(2, 0)));
+        assert_eq!(constrain(&h, 400, 100), (400, 100));
+    }
+
+    #[test]
+    fn fixed_size() {
+        assert!(is_fixed(&hints(Some((200, 100)), Some((200, 100)))));
+        assert!(!is_fixed(&hints(Some((200, 100)), Some((300, 100)))));
+        assert!(!is_fixed(&hints(Some((0, 0)), Some((0, 0)))));
+        assert!(!is_fixed(&hints(Some((200, 100)), None)));
+        assert!(!is_fixed(&WmSizeHints::new()));
+
+        let h = hints(Some((200, 100)), Some((200, 100)));
+        assert_eq!(constrain(&h, 800, 600), (200, 100));
+    }
+}
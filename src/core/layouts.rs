@@ -0,0 +1,409 @@
+use indexmap::IndexMap;
+
+use crate::core::{YATWindow, bsp::{Bsp, SplitDir}, cfgread::General};
+
+/// Screen area or window geometry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, w: u32, h: u32) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    /// Shrinks rect by `start` from left and top sides and by `end` 
+    /// from right and bottom ones, grows it where they are negative
+    pub fn shrink_sides(&self, start: i32, end: i32) -> Rect {
+        Rect::new(
+            self.x + start,
+            self.y + start,
+            (self.w as i32 - start - end).max(1) as u32,
+            (self.h as i32 - start - end).max(1) as u32,
+        )
+    }
+
+    /// Splits rect into vertical bars (left to right), sized by `weights`
+    pub fn split_cols(&self, weights: &[f32]) -> Vec<Rect> {
+        split_len(self.w, weights).into_iter()
+            .scan(self.x, |x, w| {
+                let r = Rect::new(*x, self.y, w, self.h);
+                *x += w as i32;
+                Some(r)
+            })
+            .collect()
+    }
+
+    /// Splits rect into horizontal bars (top to bottom), sized by `weights`
+    pub fn split_rows(&self, weights: &[f32]) -> Vec<Rect> {
+        split_len(self.h, weights).into_iter()
+            .scan(self.y, |y, h| {
+                let r = Rect::new(self.x, *y, self.w, h);
+                *y += h as i32;
+                Some(r)
+            })
+            .collect()
+    }
+}
+
+/// Splits `total` into parts proportional to `weights`, 
+/// the last one gets the remainder. Equal parts if weights are unusable
+fn split_len(total: u32, weights: &[f32]) -> Vec<u32> {
+    let n = weights.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let sum: f32 = weights.iter().sum();
+    if !sum.is_finite() || sum <= 0.0 {
+        return split_len(total, &vec![1.0; n]);
+    }
+
+    let mut res = Vec::with_capacity(n);
+    let mut used = 0;
+    for w in &weights[..n - 1] {
+        // float rounding mustn't take more than what's left
+        let len = ((total as f32 * w / sum) as u32).min(total - used);
+        used += len;
+        res.push(len);
+    }
+    res.push(total - used);
+    res
+}
+
+/// Applies weights to `ratio` of two areas, e.g. master/stack.
+/// Keeps `ratio` if weights are unusable
+pub fn weighted_ratio(ratio: f32, first: f32, second: f32) -> f32 {
+    let res = ratio * first / (ratio * first + (1.0 - ratio) * second);
+    if res.is_finite() { res } else { ratio }
+}
+
+fn widths(windows: &[&YATWindow]) -> Vec<f32> {
+    windows.iter().map(|w| w.weight_w).collect()
+}
+
+fn heights(windows: &[&YATWindow]) -> Vec<f32> {
+    windows.iter().map(|w| w.weight_h).collect()
+}
+
+fn mean(v: &[f32]) -> f32 {
+    v.iter().sum::<f32>() / v.len() as f32
+}
+
+/// Tiling algorithm owned by workspace
+pub trait Layout: std::fmt::Debug {
+    fn name(&self) -> &'static str;
+
+    /// Returns geometry for every visible window in `windows` placed 
+    /// in `area`. Windows missing in result are hidden
+    fn arrange(&self, area: Rect, windows: &IndexMap<u32, YATWindow>,
+        focused: Option<u32>) -> Vec<(u32, Rect)>;
+
+    /// Called when window `id` is added to workspace, `focused` is 
+    /// focused one before adding
+    fn add_wind(&mut self, _id: u32, _focused: Option<u32>) {}
+
+    /// Called when window `id` is removed from workspace
+    fn rm_wind(&mut self, _id: u32) {}
+
+    /// Handles layout-specific message. Unsupported ones are ignored
+    fn message(&mut self, _msg: &LayoutMsg, _focused: Option<u32>) {}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LayoutMsg {
+    DeltaMasterRatio(isize), // in percents
+    DeltaMasterCount(isize, usize), // delta, number of tiled windows
+    SetSplit(Option<SplitDir>), // None for automatic
+    Rotate,
+    Flip,
+    Balance,
+    Swap(u32, u32), // windows ids
+}
+
+/// Returns all built-in layouts, the order is used for cycling
+pub fn builtin_layouts(cfg: &General) -> Vec<Box<dyn Layout>> {
+    vec![
+        Box::new(Columns),
+        Box::new(Rows),
+        Box::new(MasterStack::new(
+            cfg.master_ratio.unwrap_or(0.55),
+            cfg.master_count.unwrap_or(1)
+        )),
+        Box::new(Grid),
+        Box::new(Monocle),
+        Box::new(Bsp::new()),
+    ]
+}
+
+/// Vertical bars, left to right
+#[derive(Debug)]
+pub struct Columns;
+
+impl Layout for Columns {
+    fn name(&self) -> &'static str {
+        "columns"
+    }
+
+    fn arrange(&self, area: Rect, windows: &IndexMap<u32, YATWindow>,
+        _focused: Option<u32>) -> Vec<(u32, Rect)> {
+        let wins: Vec<&YATWindow> = windows.values().collect();
+        windows.keys().copied()
+            .zip(area.split_cols(&widths(&wins)))
+            .collect()
+    }
+}
+
+/// Horizontal bars, top to bottom
+#[derive(Debug)]
+pub struct Rows;
+
+impl Layout for Rows {
+    fn name(&self) -> &'static str {
+        "rows"
+    }
+
+    fn arrange(&self, area: Rect, windows: &IndexMap<u32, YATWindow>,
+        _focused: Option<u32>) -> Vec<(u32, Rect)> {
+        let wins: Vec<&YATWindow> = windows.values().collect();
+        windows.keys().copied()
+            .zip(area.split_rows(&heights(&wins)))
+            .collect()
+    }
+}
+
+/// Master area on the left, stack on the right
+#[derive(Debug)]
+pub struct MasterStack {
+    ratio: f32, // part of area width taken by master area
+    count: usize, // windows in master area
+}
+
+impl MasterStack {
+    pub fn new(ratio: f32, count: usize) -> MasterStack {
+        MasterStack {
+            ratio: ratio.clamp(MASTER_RATIO_MIN, MASTER_RATIO_MAX),
+            count
+        }
+    }
+}
+
+impl Layout for MasterStack {
+    fn name(&self) -> &'static str {
+        "master"
+    }
+
+    fn arrange(&self, area: Rect, windows: &IndexMap<u32, YATWindow>,
+        _focused: Option<u32>) -> Vec<(u32, Rect)> {
+        let wins: Vec<&YATWindow> = windows.values().collect();
+        let n = wins.len();
+        let mcount = self.count.min(n);
+        let rects = if mcount == 0 || mcount == n {
+            area.split_rows(&heights(&wins))
+        } else {
+            let ratio = weighted_ratio(
+                self.ratio, 
+                mean(&widths(&wins[..mcount])), 
+                mean(&widths(&wins[mcount..]))
+            );
+            let mwidth = (area.w as f32 * ratio) as u32;
+            let master = Rect::new(area.x, area.y, mwidth, area.h);
+            let stack = Rect::new(
+                area.x + mwidth as i32,
+                area.y,
+                area.w - mwidth,
+                area.h
+            );
+
+            let mut res = master.split_rows(&heights(&wins[..mcount]));
+            res.extend(stack.split_rows(&heights(&wins[mcount..])));
+            res
+        };
+
+        windows.keys().copied().zip(rects).collect()
+    }
+
+    fn message(&mut self, msg: &LayoutMsg, _focused: Option<u32>) {
+        match msg {
+            LayoutMsg::DeltaMasterRatio(delta) => {
+                self.ratio = (self.ratio + *delta as f32 / 100.0)
+                    .clamp(MASTER_RATIO_MIN, MASTER_RATIO_MAX);
+            }
+            LayoutMsg::DeltaMasterCount(delta, tiled) => {
+                // more than all windows would need as many decrements
+                // to take effect again
+                self.count = self.count.min(*tiled)
+                    .saturating_add_signed(*delta)
+                    .min(*tiled);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Rows of equal columns, as square as possible
+#[derive(Debug)]
+pub struct Grid;
+
+impl Layout for Grid {
+    fn name(&self) -> &'static str {
+        "grid"
+    }
+
+    fn arrange(&self, area: Rect, windows: &IndexMap<u32, YATWindow>,
+        _focused: Option<u32>) -> Vec<(u32, Rect)> {
+        let wins: Vec<&YATWindow> = windows.values().collect();
+        let n = wins.len();
+        if n == 0 {
+            return Vec::new();
+        }
+
+        let cols = (n as f32).sqrt().ceil() as usize;
+        // last row takes the rest
+        let rows: Vec<&[&YATWindow]> = wins.chunks(cols).collect();
+        let row_weights: Vec<f32> = rows.iter()
+            .map(|r| mean(&heights(r)))
+            .collect();
+
+        let mut rects = Vec::with_capacity(n);
+        for (row, rwins) in area.split_rows(&row_weights).iter().zip(rows) {
+            rects.extend(row.split_cols(&widths(rwins)));
+        }
+
+        windows.keys().copied().zip(rects).collect()
+    }
+}
+
+/// Only focused window is shown and takes the whole area
+#[derive(Debug)]
+pub struct Monocle;
+
+impl Layout for Monocle {
+    fn name(&self) -> &'static str {
+        "monocle"
+    }
+
+    fn arrange(&self, area: Rect, windows: &IndexMap<u32, YATWindow>,
+        focused: Option<u32>) -> Vec<(u32, Rect)> {
+        focused.filter(|id| windows.contains_key(id))
+            .or(windows.keys().next().copied())
+            .map(|id| vec![(id, area)])
+            .unwrap_or_default()
+    }
+}
+
+const MASTER_RATIO_MIN: f32 = 0.1;
+const MASTER_RATIO_MAX: f32 = 0.9;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_len_sums_to_total() {
+        let cases: [&[f32]; 5] = [
+            &[1.0],
+            &[1.0, 1.0, 1.0],
+            &[0.1, 10.0, 3.3],
+            &[0.3, 0.3, 0.3, 0.3, 0.3, 0.3, 0.3],
+            &[2.5, 0.1],
+        ];
+        for total in [0, 1, 7, 100, 1079, 3840] {
+            for weights in cases {
+                let res = split_len(total, weights);
+                assert_eq!(res.len(), weights.len());
+                assert_eq!(res.iter().sum::<u32>(), total,
+                    "{:?} of {}", weights, total);
+            }
+        }
+    }
+
+    #[test]
+    fn split_len_proportional() {
+        assert_eq!(split_len(100, &[1.0, 1.0]), vec![50, 50]);
+        assert_eq!(split_len(100, &[3.0, 1.0]), vec![75, 25]);
+        // remainder goes to the last one
+        assert_eq!(split_len(10, &[1.0, 1.0, 1.0]), vec![3, 3, 4]);
+        assert!(split_len(100, &[]).is_empty());
+    }
+
+    #[test]
+    fn split_len_bad_weights() {
+        assert_eq!(split_len(90, &[0.0, 0.0, 0.0]), vec![30, 30, 30]);
+        assert_eq!(split_len(90, &[f32::MAX, f32::MAX, 1.0]), 
+            vec![30, 30, 30]);
+        assert_eq!(split_len(100, &[1e30, 1.0]), vec![100, 0]);
+        assert_eq!(split_len(100, &[0.0, 1.0]), vec![0, 100]);
+    }
+
+    #[test]
+    fn weighted_ratio_values() {
+        assert_eq!(weighted_ratio(0.5, 1.0, 1.0), 0.5);
+        assert_eq!(weighted_ratio(0.3, 2.0, 2.0), 0.3);
+        assert!((weighted_ratio(0.5, 3.0, 1.0) - 0.75).abs() < 1e-6);
+        assert_eq!(weighted_ratio(0.5, 0.0, 1.0), 0.0);
+        assert_eq!(weighted_ratio(0.5, 1.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn master_count_clamped() {
+        let mut ms = MasterStack::new(0.5, 1);
+        for _ in 0..5 {
+            ms.message(&LayoutMsg::DeltaMasterCount(1, 3), None);
+        }
+        assert_eq!(ms.count, 3);
+        ms.message(&LayoutMsg::DeltaMasterCount(-1, 3), None);
+        assert_eq!(ms.count, 2);
+        ms.message(&LayoutMsg::DeltaMasterCount(-5, 3), None);
+        assert_eq!(ms.count, 0);
+
+        // windows closed meanwhile
+        let mut ms = MasterStack::new(0.5, 4);
+        ms.message(&LayoutMsg::DeltaMasterCount(-1, 2), None);
+        assert_eq!(ms.count, 1);
+    }
+
+    #[test]
+    fn odd_inner_gap_exact() {
+        // the way tiles are shrunk by inner gap
+        let (outer, inner) = (10, 5);
+        let (start, end) = (inner / 2, inner - inner / 2);
+        let area = Rect::new(0, 0, 100, 60)
+            .shrink_sides(outer - start, outer - end);
+        let tiles: Vec<Rect> = area.split_cols(&[1.0, 1.0]).into_iter()
+            .map(|r| r.shrink_sides(start, end))
+            .collect();
+
+        assert_eq!(tiles[0].x, outer);
+        assert_eq!(tiles[0].y, outer);
+        assert_eq!(tiles[1].x - (tiles[0].x + tiles[0].w as i32), inner);
+        assert_eq!(tiles[1].x + tiles[1].w as i32, 100 - outer);
+        assert_eq!(tiles[1].y + tiles[1].h as i32, 60 - outer);
+    }
+
+    #[test]
+    fn monocle_missing_focused() {
+        let windows: IndexMap<u32, YATWindow> = [2, 3].into_iter()
+            .map(|id| (id, YATWindow::new(id, 0, 0)))
+            .collect();
+        let area = Rect::new(0, 0, 100, 100);
+        assert_eq!(Monocle.arrange(area, &windows, Some(3)), 
+            vec![(3, area)]);
+        // e.g. floating one, not passed to layouts
+        assert_eq!(Monocle.arrange(area, &windows, Some(42)), 
+            vec![(2, area)]);
+        assert_eq!(Monocle.arrange(area, &windows, None), vec![(2, area)]);
+        assert!(Monocle.arrange(area, &IndexMap::new(), Some(2)).is_empty());
+    }
+
+    #[test]
+    fn weighted_ratio_bad_weights() {
+        assert_eq!(weighted_ratio(0.6, 0.0, 0.0), 0.6);
+        assert_eq!(weighted_ratio(0.6, f32::INFINITY, f32::INFINITY), 0.6);
+        let r = weighted_ratio(0.5, f32::MAX, 1.0);
+        assert!((0.0..=1.0).contains(&r));
+    }
+}
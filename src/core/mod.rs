@@ -3,20 +3,33 @@ use std::{collections::HashMap, path::PathBuf, str::FromStr};
 use indexmap::IndexMap;
 use log::{error, info, warn};
 use maplit::hashmap;
-use x11rb::{COPY_DEPTH_FROM_PARENT, connection::Connection, protocol::{Event, xproto::{ButtonIndex, ChangeWindowAttributesAux, ConfigureWindowAux, ConnectionExt, CreateWindowAux, EventMask, GrabMode, InputFocus, ModMask, Screen, WindowClass}}, rust_connection::RustConnection};
+use x11rb::{connection::Connection, errors::ReplyError, properties::WmSizeHints, protocol::{ErrorKind, Event, xproto::{AtomEnum, ButtonIndex, ChangeWindowAttributesAux, ClientMessageEvent, ConfigureWindowAux, ConnectionExt, EventMask, GrabMode, InputFocus, MapState, ModMask, PropertyNotifyEvent, Screen, StackMode}}, rust_connection::RustConnection};
 
 use crate::core::{
-    cfgread::{ActionEnum, ActionValue, CfgMacro, Config, keycode_to_keysym, keysym_to_keycode}, input::{InputCt, KeyRange, Keycut}, workspaces::Workspace
+    atoms::Atoms, cfgread::{ActionEnum, ActionValue, CfgMacro, Config, FocusSteal, General, keycode_to_keysym, keysym_to_keycode}, input::{InputCt, KeyRange, Keycut}, bsp::SplitDir, ewmh::WindowKind, layouts::{LayoutMsg, Rect}, workspaces::Workspace
 };
 
+pub mod atoms;
+pub mod bsp;
 pub mod cfgread;
+pub mod ewmh;
+pub mod icccm;
 pub mod input;
+pub mod layouts;
 pub mod workspaces;
 
 const YATWM_DEF_LOGF: &str = ".local/state/yatwm.log"; // in homedir. prepend home 
 
+// _NET_WM_STATE client message actions
+const NET_WM_STATE_REMOVE: u32 = 0;
+const NET_WM_STATE_ADD: u32 = 1;
+
+// _NET_ACTIVE_WINDOW source indication
+const NET_SOURCE_PAGER: u32 = 2;
+
 pub struct WM {
     cfg: Config,
+    scr_num: usize,
     state: YATState<RustConnection>
 }
 
@@ -39,18 +52,47 @@ impl WM {
 
         WM {
             cfg: cfg,
+            scr_num,
             state: state
         }
     }
 
-    pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        self.state.conn.change_window_attributes(
+    /// Runs event loop. If `replace`, takes over from running 
+    /// ICCCM-compliant WM instead of failing
+    pub fn run(&mut self, replace: bool) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let (sel_owner, time, pending) = icccm::acquire_wm_selection(
+            &self.state.conn, 
+            &self.state.atoms, 
+            &self.state.screen, 
+            self.scr_num, 
+            replace
+        )?;
+        self.state.sel_owner = sel_owner;
+        // focusing adopted windows needs real time already
+        self.state.time = time;
+
+        // only one client can select SubstructureRedirect on root
+        let redirect = self.state.conn.change_window_attributes(
             self.state.screen.root,
             &ChangeWindowAttributesAux::new().event_mask(
                 EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY
                 | EventMask::ENTER_WINDOW
             ),
-        )?;
+        )?.check();
+        match redirect {
+            Err(ReplyError::X11Error(e)) if e.error_kind == ErrorKind::Access => {
+                return Err(Box::new(CustomError {
+                    message: "another window manager is already running"
+                        .to_owned()
+                }));
+            }
+            other => other?,
+        }
+
+        ewmh::init(&self.state.conn, &self.state.screen, &self.state.atoms)?;
+        self.state.update_desktops()?;
+        self.state.adopt_existing()?;
 
         self.state.conn.grab_button(
             true,               
@@ -64,8 +106,16 @@ impl WM {
             ModMask::ANY,       
         )?;
 
+        self.state.grab_resize()?;
         self.state.conn.flush()?;
 
+        // received while waiting for the old WM
+        for ev in pending {
+            if let Err(e) = self.state.handle_event(ev) {
+                error!("{}", e);
+            }
+        }
+
         if let Some(sv) = self.cfg.general.autostart.as_ref() {
             for cmd in sv {
                 self.state.inpct.run_cmd(&cmd); 
@@ -80,6 +130,11 @@ impl WM {
            if let Err(e) = self.state.handle_event(ev) {
                 error!("{}", e);
            };
+
+            if self.state.replaced {
+                info!("Replaced by another window manager, exiting");
+                return Ok(());
+            }
         }
 
         //Ok(())
@@ -111,16 +166,43 @@ pub struct YATState<C: Connection> {
     inpct: InputCt,
     focus_new: bool, 
     macros: HashMap<String, CfgMacro>,
+    general: General,
+    drag: Option<Drag>, // window being moved or resized by mouse
+    atoms: Atoms,
+    clients: Vec<u32>, // managed windows in mapping order
+    stacking: Vec<u32>, // managed windows from bottom to top
+    urgent: Vec<u32>, // urgent windows, the oldest first
+    time: u32, // server time of the last event that had it
+    sel_owner: u32, // window owning WM_S<n> selection
+    replaced: bool, // another WM took the selection
+    docks: HashMap<u32, [u32; 4]>, // unmanaged bars and panels with 
+                                   // space they reserve at left, right, 
+                                   // top and bottom edges
+}
+
+/// Mouse drag in progress
+#[derive(Debug)]
+struct Drag {
+    wind: u32,
+    button: u8, // 1 moves, 3 resizes
+    start_x: i16, // pointer pos on root
+    start_y: i16,
+    orig: Rect, // window geometry at start
+    tiled: bool, // tiled window dragged in swap mode
 }
 
 impl<C: Connection> YATState<C> {
     pub fn new(conn: C, scr_num: usize, cfg: &Config) -> YATState<C> {
         let scr = conn.setup().roots[scr_num].clone();
+        let atoms = Atoms::new(&conn)
+            .expect("Failed to intern atoms")
+            .reply()
+            .expect("Failed to intern atoms");
         
         let mut workspaces = HashMap::new();
         let wrksps_ct = cfg.general.def_wrksp_ctr.unwrap_or(1);
         for i in 1..(wrksps_ct + 1) { 
-            let workspace = Workspace::new(i);
+            let workspace = Workspace::new(i, &cfg.general);
             workspaces.insert(i, workspace);
         }
 
@@ -132,92 +214,59 @@ impl<C: Connection> YATState<C> {
             focus_new: cfg.general.focus_new.unwrap_or(true),
             workspaces: workspaces,
             macros: cfg.macros.clone().unwrap_or(HashMap::new()),
+            general: cfg.general.clone(),
+            drag: None,
+            atoms,
+            clients: Vec::new(),
+            stacking: Vec::new(),
+            urgent: Vec::new(),
+            time: x11rb::CURRENT_TIME,
+            sel_owner: x11rb::NONE,
+            replaced: false,
+            docks: HashMap::new(),
         }
     }
 
     fn handle_event(&mut self, ev: Event) 
             -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(time) = event_time(&ev) {
+            self.time = time;
+        }
+
+        if self.is_foreign(&ev) {
+            return Ok(());
+        }
+
         match ev {
             Event::ConfigureRequest(e) => {
                 let aux = ConfigureWindowAux::from_configure_request(&e);
 
+                // floating windows remember geometry they ask for
+                if let Some(wind) = self.find_wind_mut(e.window) 
+                    && wind.floating {
+                    let mut r = wind.float_rect
+                        .unwrap_or(Rect::new(0, 0, 1, 1));
+                    r.x = aux.x.unwrap_or(r.x);
+                    r.y = aux.y.unwrap_or(r.y);
+                    r.w = aux.width.unwrap_or(r.w);
+                    r.h = aux.height.unwrap_or(r.h);
+                    wind.float_rect = Some(r);
+                }
+
                 self.conn.configure_window(e.window, &aux)?;
                 self.conn.flush()?;
             }
             Event::MapRequest(e) => {
-                let new_win = YATWindow::new(
-                    e.window, 0, 0 // would be updated anyways
-                );
-
-                let cur_wrksp = self.workspaces
-                    .get_mut(&self.cur_scr).ok_or(CustomError {
-                        message: "Can't get cur workspace".to_owned()
-                     })?;
-                cur_wrksp.add_wind(e.window, new_win);
-                let cur_wrksp_len = cur_wrksp.windows.len();
-
-                let (x, y, w, h) = self.calc_cords(0)?;
-                
-                
-
-                self.conn.configure_window(
-                    e.window,
-                    &ConfigureWindowAux::new()
-                        .x(x as i32)
-                        .y(y as i32)
-                        .width(w)
-                        .height(h)
-                )?;
-
-                let event_mask = EventMask::EXPOSURE
-                    | EventMask::STRUCTURE_NOTIFY
-                    | EventMask::PROPERTY_CHANGE
-                    | EventMask::BUTTON_PRESS    
-                    | EventMask::BUTTON_RELEASE  
-                    | EventMask::POINTER_MOTION  
-                    | EventMask::ENTER_WINDOW   
-                    | EventMask::LEAVE_WINDOW
-                    | EventMask::FOCUS_CHANGE;   
-
-                self.conn.change_window_attributes(
-                    e.window,
-                    &ChangeWindowAttributesAux::default()
-                        .event_mask(event_mask)
-                )?;
-
-                self.conn.map_window(e.window)?;
-
-                // if the window is only one or focus_new, focus it
-                if cur_wrksp_len == 1 || self.focus_new {
-                    self.conn.set_input_focus(
-                        InputFocus::PARENT, 
-                        e.window, 
-                        x11rb::CURRENT_TIME
-                    )?;
-                }
-
-                self.conn.flush()?;
+                self.manage_wind(e.window, None)?;
             }
             Event::DestroyNotify(e) => {
-                self.rm_any_wind(e.window);
-                self.update_all_sizes(0)?; // already removed thus 0 
-
-                let cur_wrksp = self.workspaces
-                    .get_mut(&self.cur_scr).ok_or(CustomError {
-                    message: "Can't get cur workspace".to_owned()
-                 })?;
-
-                // if only one window left, focus it
-                if cur_wrksp.windows.len() == 1 && let Some(w) 
-                =  cur_wrksp.windows.get_index(0) {
-
-                    self.conn.set_input_focus(
-                        InputFocus::PARENT,
-                        w.1.id, 
-                        x11rb::CURRENT_TIME
-                    )?;
-                    self.conn.flush()?;
+                if self.docks.remove(&e.window).is_some() {
+                    self.update_workarea()?;
+                    self.update_all_sizes()?;
+                    return Ok(());
                 }
+
+                self.unmanage(e.window)?;
             }
             Event::KeyRelease(e) => {
                 let ks_opt = keycode_to_keysym(&self.conn, e.detail);
@@ -250,19 +299,98 @@ impl<C: Connection> YATState<C> {
                 }
             }
             Event::ButtonPress(e) => {
-                // left mouse button 
-                if e.detail == 1 {
-                    let win_id = e.child;
+                let with_mod = self.general.mainmod_mask()
+                    .is_some_and(|m| u16::from(e.state) & u16::from(m) != 0);
+                let managed = self.find_wind_mut(e.child).is_some();
 
-                    self.conn.set_input_focus(
-                        InputFocus::PARENT, 
-                        win_id,
-                        x11rb::CURRENT_TIME,
-                    )?;
+                if with_mod && managed && (e.detail == 1 || e.detail == 3) {
+                    self.start_drag(e.child, e.detail, e.root_x, e.root_y)?;
+                } else if e.detail == 1 && managed {
+                    // left mouse button 
+                    self.focus_wind(e.child, e.time)?;
 
                     self.conn.flush()?;
                 } 
             }
+            Event::MotionNotify(e) => {
+                let Some(drag) = &self.drag else {
+                    return Ok(());
+                };
+                if drag.tiled {
+                    return Ok(()); // handled on release
+                }
+
+                let dx = (e.root_x - drag.start_x) as i32;
+                let dy = (e.root_y - drag.start_y) as i32;
+                let mut r = drag.orig;
+                if drag.button == 1 {
+                    r.x += dx;
+                    r.y += dy;
+                } else {
+                    r.w = (r.w as i32 + dx).max(1) as u32;
+                    r.h = (r.h as i32 + dy).max(1) as u32;
+                }
+                let id = drag.wind;
+                if let Some(hints) = self.find_wind_mut(id)
+                    .and_then(|w| w.size_hints) {
+                    (r.w, r.h) = icccm::constrain(&hints, r.w, r.h);
+                }
+
+                if let Some(wind) = self.find_wind_mut(id) {
+                    wind.float_rect = Some(r);
+                }
+                self.conn.configure_window(
+                    id,
+                    &ConfigureWindowAux::new()
+                        .x(r.x)
+                        .y(r.y)
+                        .width(r.w)
+                        .height(r.h)
+                )?;
+                self.conn.flush()?;
+            }
+            Event::ClientMessage(e) => {
+                self.handle_client_msg(e)?;
+            }
+            Event::SelectionClear(e) if e.owner == self.sel_owner => {
+                self.replaced = true;
+            }
+            Event::PropertyNotify(e) => {
+                self.handle_property(e)?;
+            }
+            Event::UnmapNotify(e) if self.docks.contains_key(&e.window) => {
+                self.docks.remove(&e.window);
+                self.update_workarea()?;
+                self.update_all_sizes()?;
+            }
+            // every unmap comes both to root and to the window itself
+            Event::UnmapNotify(e) if e.event == self.screen.root => {
+                let Some(wind) = self.find_wind_mut(e.window) else {
+                    return Ok(());
+                };
+                if wind.ignore_unmaps > 0 {
+                    // caused by us, e.g. workspace switch
+                    wind.ignore_unmaps -= 1;
+                    return Ok(());
+                }
+
+                // client withdrew the window, it's managed again on map
+                icccm::set_wm_state(&self.conn, &self.atoms, e.window, 
+                    icccm::WM_STATE_WITHDRAWN)?;
+                ewmh::clear_wind(&self.conn, &self.atoms, e.window)?;
+                self.unmanage(e.window)?;
+            }
+            Event::ButtonRelease(e) => {
+                if self.drag.as_ref().is_some_and(|d| d.button == e.detail) 
+                    && let Some(drag) = self.drag.take() {
+                    self.conn.ungrab_pointer(x11rb::CURRENT_TIME)?;
+                    if drag.tiled {
+                        self.end_tiled_drag(&drag, e.child, e.root_x, 
+                            e.root_y)?;
+                    }
+                    self.conn.flush()?;
+                }
+            }
             other => {
                 // TODO
             }
@@ -270,7 +398,142 @@ impl<C: Connection> YATState<C> {
         Ok(())
     }
 
+    /// Handles changes of window properties
+    fn handle_property(&mut self, e: PropertyNotifyEvent) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        if e.atom == u32::from(AtomEnum::WM_HINTS) 
+            && self.find_wind_mut(e.window).is_some() {
+            let hints = icccm::get_wm_hints(&self.conn, e.window)?;
+            if let Some(wind) = self.find_wind_mut(e.window) {
+                wind.input = icccm::accepts_input(hints.as_ref());
+            }
+            let urgent = hints.is_some_and(|h| h.urgent);
+            self.set_urgent(e.window, urgent)?;
+            self.conn.flush()?;
+            return Ok(());
+        }
+        if e.atom == self.atoms._NET_WM_STATE 
+            && let Some(cur) = self.find_wind_mut(e.window).map(|w| w.urgent) {
+            let urgent = ewmh::get_atoms(&self.conn, e.window, e.atom)?
+                .contains(&self.atoms._NET_WM_STATE_DEMANDS_ATTENTION);
+            // our own updates of the property come here too
+            if urgent != cur {
+                self.set_urgent(e.window, urgent)?;
+                self.conn.flush()?;
+            }
+            return Ok(());
+        }
+        if e.atom == self.atoms.WM_PROTOCOLS 
+            && self.find_wind_mut(e.window).is_some() {
+            let protocols = icccm::get_protocols(
+                &self.conn, 
+                &self.atoms, 
+                e.window
+            )?;
+            if let Some(wind) = self.find_wind_mut(e.window) {
+                wind.protocols = protocols;
+            }
+            return Ok(());
+        }
+        if e.atom == u32::from(AtomEnum::WM_NORMAL_HINTS) 
+            && self.find_wind_mut(e.window).is_some() {
+            let hints = icccm::get_size_hints(&self.conn, e.window)?;
+            if let Some(wind) = self.find_wind_mut(e.window) {
+                wind.size_hints = hints;
+            }
+            self.update_all_sizes()?;
+            return Ok(());
+        }
+
+        if self.docks.contains_key(&e.window) 
+            && (e.atom == self.atoms._NET_WM_STRUT 
+                || e.atom == self.atoms._NET_WM_STRUT_PARTIAL) {
+            let strut = self.dock_strut(e.window)?;
+            self.docks.insert(e.window, strut);
+            self.update_workarea()?;
+            self.update_all_sizes()?;
+        }
+        Ok(())
+    }
+
+    /// Handles requests sent by clients and pagers to root window
+    fn handle_client_msg(&mut self, e: ClientMessageEvent) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let data = e.data.as_data32();
+
+        if e.type_ == self.atoms._NET_ACTIVE_WINDOW {
+            // [source, timestamp, requestor's active window]
+            if self.find_wind_mut(e.window).is_none() {
+                return Ok(());
+            }
+            if data[1] != x11rb::CURRENT_TIME {
+                self.time = data[1];
+            }
+
+            // pagers act on behalf of the user
+            if data[0] == NET_SOURCE_PAGER {
+                self.activate(e.window)?;
+                return Ok(());
+            }
+            match self.general.focus_steal.unwrap_or_default() {
+                FocusSteal::Allow => self.activate(e.window)?,
+                FocusSteal::Urgent => {
+                    self.set_urgent(e.window, true)?;
+                    self.conn.flush()?;
+                }
+                FocusSteal::Deny => info!("Denied activation of {}", e.window),
+            }
+        } else if e.type_ == self.atoms._NET_CLOSE_WINDOW {
+            if self.find_wind_mut(e.window).is_some() {
+                self.close_wind(e.window)?;
+            }
+        } else if e.type_ == self.atoms._NET_CURRENT_DESKTOP {
+            // pagers can send anything, don't create workspaces for them
+            let wrksp = (data[0] as usize).saturating_add(1);
+            if self.workspaces.contains_key(&wrksp) {
+                self.change_workspace(wrksp)?;
+            } else {
+                warn!("Ignored switch to desktop {}", data[0]);
+            }
+        } else if e.type_ == self.atoms._NET_WM_DESKTOP {
+            // 0xFFFFFFFF means all desktops, which isn't supported
+            let wrksp = (data[0] as usize).saturating_add(1);
+            if self.workspaces.contains_key(&wrksp) {
+                self.move_to_wrksp(e.window, wrksp)?;
+            } else {
+                warn!("Ignored move of {} to desktop {}", e.window, data[0]);
+            }
+        } else if e.type_ == self.atoms._NET_WM_STATE {
+            // [action, first prop, second prop, source]
+            let Some(wind) = self.find_wind_mut(e.window).cloned() else {
+                return Ok(());
+            };
+            let apply = |cur: bool| match data[0] {
+                NET_WM_STATE_REMOVE => false,
+                NET_WM_STATE_ADD => true,
+                _ => !cur, // toggle
+            };
+
+            for prop in [data[1], data[2]] {
+                if prop == self.atoms._NET_WM_STATE_FULLSCREEN {
+                    self.set_fullscreen(e.window, apply(wind.fullscreen))?;
+                } else if prop == self.atoms._NET_WM_STATE_DEMANDS_ATTENTION {
+                    self.set_urgent(e.window, apply(wind.urgent))?;
+                    self.conn.flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn rm_any_wind(&mut self, idx: u32) -> Option<YATWindow> {
+        self.clients.retain(|c| *c != idx);
+        self.stacking.retain(|c| *c != idx);
+        self.urgent.retain(|c| *c != idx);
+        if let Err(e) = self.update_client_list() {
+            error!("Failed to update client list: {}", e);
+        }
+
         for (i, wrksp) in self.workspaces.iter_mut() {
             if let Some(v) = wrksp.rm_wind(idx as u32) {
                 return Some(v);
@@ -279,6 +542,33 @@ impl<C: Connection> YATState<C> {
         None
     }
 
+    /// Publishes _NET_CLIENT_LIST and _NET_CLIENT_LIST_STACKING
+    fn update_client_list(&self) -> Result<(), Box<dyn std::error::Error>> {
+        ewmh::set_client_list(
+            &self.conn, 
+            &self.atoms, 
+            self.screen.root, 
+            &self.clients, 
+            &self.stacking
+        )?;
+        Ok(())
+    }
+
+    /// Raises window `id` above others and keeps stacking list in sync
+    fn raise_wind(&mut self, id: u32) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        self.conn.configure_window(
+            id,
+            &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE)
+        )?;
+
+        if let Some(pos) = self.stacking.iter().position(|c| *c == id) {
+            let id = self.stacking.remove(pos);
+            self.stacking.push(id);
+        }
+        Ok(())
+    }
+
     fn change_workspace(&mut self, new_id: usize) 
         -> Result<(), Box<dyn std::error::Error>> {
         
@@ -286,50 +576,144 @@ impl<C: Connection> YATState<C> {
             message: "Failed to get cur workspace".to_owned()
         })?;
 
-        for (i, wind) in &cur_wrksp.windows {
-            self.conn.unmap_window(wind.id)?;
+        let hide: Vec<u32> = cur_wrksp.windows.keys().copied().collect();
+        for id in hide {
+            self.hide_wind(id)?;
         }
         
+        let mut focus = None;
         match self.workspaces.get(&new_id) {
             Some(v) => {
                 info!("found workpace {}, win len: {}", new_id, v.windows.len());
-                for (i, wind) in &v.windows {
-                    self.conn.map_window(wind.id)?;
-                    if v.windows.len() == 1 {
-                        self.conn.set_input_focus(
-                            InputFocus::PARENT,
-                            wind.id,
-                            x11rb::CURRENT_TIME
-                        )?;
-                    }
-                    info!("mapping wind {}", wind.id);
-                }
+                // windows moved or adopted here were never focused
+                focus = v.focused.or(v.windows.keys().next().copied());
             }
             None => {
-                let wrksp = Workspace::new(new_id);
+                let wrksp = Workspace::new(new_id, &self.general);
                 self.workspaces.insert(new_id, wrksp);
                 warn!("creating new workspace");
             }
         };
 
         self.cur_scr = new_id;
-        self.update_all_sizes(0)?;
+        self.update_desktops()?;
+        // maps windows visible in the new workspace layout
+        self.update_all_sizes()?;
+        match focus {
+            Some(id) => self.focus_wind(id, self.time)?,
+            None => self.unfocus()?,
+        }
         self.conn.flush()?;
 
         Ok(())
     }
 
+    /// Moves window `id` from its workspace to `new_id`-th one
+    fn move_to_wrksp(&mut self, id: u32, new_id: usize) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        // lets check if there is a new workspace 
+        // so we dont just remove window in case of some trouble
+        self.workspaces 
+            .get(&new_id)
+            .ok_or(CustomError {
+                message: format!("Can't get {}-th workspace", new_id)
+            })?;
+
+        let old_id = self.wrksp_of(id).ok_or(CustomError {
+            message: format!("Window {} isn't managed", id)
+        })?;
+        if old_id == new_id {
+            return Ok(());
+        }
+
+        let removed = self.workspaces
+            .get_mut(&old_id)
+            .and_then(|w| w.rm_wind(id))
+            .ok_or(CustomError {
+                message: format!("Seems like window {} isn't in \
+                             {}-th workspace!", id, old_id) 
+            })?;
+        
+        let new_worksp = self.workspaces 
+            .get_mut(&new_id)
+            .ok_or(CustomError {
+                message: format!("Can't get {}-th workspace", new_id)
+            })?;
+        new_worksp.add_wind(id, removed);
+        if new_id != self.cur_scr {
+            self.hide_wind(id)?;
+        }
+        self.update_client_list()?;
+        ewmh::set_wm_desktop(&self.conn, &self.atoms, id, 
+            desktop_of(new_id))?;
+
+        self.update_all_sizes()?;
+        if let Some(id) = self.cur_wrksp_mut()?.focused {
+            self.focus_wind(id, self.time)?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Whether `ev` is an unmap or destroy of a window that isn't 
+    /// managed, e.g. override-redirect menus, tooltips and drag icons. 
+    /// Those must not touch the layout
+    fn is_foreign(&self, ev: &Event) -> bool {
+        let id = match ev {
+            Event::UnmapNotify(e) => e.window,
+            Event::DestroyNotify(e) => e.window,
+            _ => return false,
+        };
+        !self.docks.contains_key(&id) && self.wrksp_of(id).is_none()
+    }
+
+    /// Returns index of workspace containing window `id`
+    fn wrksp_of(&self, id: u32) -> Option<usize> {
+        self.workspaces.values()
+            .find(|w| w.windows.contains_key(&id))
+            .map(|w| w.idx)
+    }
+
+    /// Publishes workspaces as EWMH desktops. Their count is the 
+    /// highest workspace index so desktops numbers match
+    fn update_desktops(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let count = self.desktops_count();
+        let names: Vec<String> = (1..=count).map(|i| i.to_string()).collect();
+
+        ewmh::set_desktops(
+            &self.conn, 
+            &self.atoms, 
+            self.screen.root, 
+            &names, 
+            desktop_of(self.cur_scr)
+        )?;
+        self.update_workarea()?;
+        Ok(())
+    }
+
+    /// Number of EWMH desktops, workspaces 1..=N are published
+    fn desktops_count(&self) -> usize {
+        self.workspaces.keys().copied().max().unwrap_or(1).max(1)
+    }
+
+    /// Publishes _NET_WORKAREA, same for every desktop
+    fn update_workarea(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let count = self.desktops_count();
+        ewmh::set_workarea(
+            &self.conn, 
+            &self.atoms, 
+            self.screen.root, 
+            self.work_area(), 
+            count
+        )?;
+        Ok(())
+    }
+
     /// Register shortcuts from config
     fn reg_scuts(&mut self, cfg: &Config) {
-        let mainmod = match cfg.general.mainmod.to_lowercase().as_str() {
-            "super" | "win" => ModMask::M4,
-            "alt" => ModMask::M1,
-            "shift" => ModMask::SHIFT,
-            "ctrl" | "control" => ModMask::CONTROL,
-            other => {
-                error!("Unknown mainmod {}", other);
-                return;
-            }
+        let Some(mainmod) = cfg.general.mainmod_mask() else {
+            error!("Unknown mainmod {}", cfg.general.mainmod);
+            return;
         };
 
         for (key, val) in &cfg.shortcuts {
@@ -573,51 +957,26 @@ impl<C: Connection> YATState<C> {
                 info!("moving wind {} to {}-th workspace, cur: {}",
                     focused_id, new_id, self.cur_scr);
 
-                // lets check if there is a new workspace 
-                // so we dont just remove window in case of some trouble
-                self.workspaces 
-                    .get(new_id)
-                    .ok_or(CustomError {
-                        message: format!("Can't get current ({}) workspace",
-                                     self.cur_scr)
-                    })?;
-
-                
-                let cur_wrksp = self.workspaces
-                    .get_mut(&self.cur_scr)
-                    .ok_or(CustomError {
-                        message: format!("Can't get current ({}) workspace",
-                                     self.cur_scr)
-                    })?;
-                let removed = cur_wrksp.windows.shift_remove(&focused_id)
-                    .ok_or(CustomError {
-                    message: format!("Seems like window {} isn't in \
-                                 current workspace!", focused_id) 
-                })?;
-                
-                let new_worksp = self.workspaces 
-                    .get_mut(new_id)
-                    .ok_or(CustomError {
-                        message: format!("Can't get current ({}) workspace",
-                                     self.cur_scr)
-                    })?;
-                self.conn.unmap_window(removed.id)?;
-
-                new_worksp.windows.insert(focused_id, removed);
-
-                self.update_all_sizes(0)?;
+                if !self.cur_wrksp_mut()?.windows.contains_key(&focused_id) {
+                    return Err(Box::new(CustomError {
+                        message: format!("Seems like window {} isn't in \
+                                     current workspace!", focused_id) 
+                    }));
+                }
+                self.move_to_wrksp(focused_id, *new_id)?;
             }
             ActionEnum::FocusOther(delta) => {
-                let focus_id = self.conn.get_input_focus()?
-                    .reply()?
-                    .focus;
-
                 let cur_wrksp = self.workspaces.get(&self.cur_scr)
                     .ok_or(CustomError {
                         message: format!("Can't get cur workspace {}",
                                      self.cur_scr)
                     })?;
 
+                let focus_id = match cur_wrksp.focused {
+                    Some(v) => v,
+                    None => self.conn.get_input_focus()?.reply()?.focus
+                };
+
                 let idx = cur_wrksp.windows.get_index_of(&focus_id)
                     .ok_or(CustomError {
                         message: format!("Can't get window {} in cur workspace",
@@ -637,15 +996,146 @@ impl<C: Connection> YATState<C> {
                         (&0, &placehold)
                     });
                 if flag {return Ok(());} // avoiding errors spam
+                let new_focus = *new_focus.0;
 
-                self.conn.set_input_focus(
-                    InputFocus::PARENT, 
-                    *new_focus.0,
-                    x11rb::CURRENT_TIME
-                )?;
+                // layout could show only focused window (e.g. monocle), 
+                // it's raised as well
+                self.cur_wrksp_mut()?.set_focused(new_focus);
+                self.update_all_sizes()?;
+                self.focus_wind(new_focus, self.time)?;
 
                 self.conn.flush()?;
             }
+            ActionEnum::SetLayout(name) => {
+                if !self.cur_wrksp_mut()?.set_layout(name) {
+                    return Err(Box::new(CustomError {
+                        message: format!("Unknown layout {}", name)
+                    }));
+                }
+                self.update_all_sizes()?;
+            }
+            ActionEnum::CycleLayout(delta) => {
+                let cur_wrksp = self.cur_wrksp_mut()?;
+                cur_wrksp.cycle_layout(*delta);
+                info!("workspace {} layout: {}", cur_wrksp.idx, 
+                    cur_wrksp.layout().name());
+                self.update_all_sizes()?;
+            }
+            ActionEnum::DeltaMasterRatio(delta) => {
+                self.cur_wrksp_mut()?
+                    .layout_msg(&LayoutMsg::DeltaMasterRatio(*delta));
+                self.update_all_sizes()?;
+            }
+            ActionEnum::DeltaMasterCount(delta) => {
+                let wrksp = self.cur_wrksp_mut()?;
+                let tiled = wrksp.tiled().len();
+                wrksp.layout_msg(&LayoutMsg::DeltaMasterCount(*delta, tiled));
+                self.update_all_sizes()?;
+            }
+            ActionEnum::SetSplitDir(name) => {
+                let dir = match name.to_lowercase().as_str() {
+                    "auto" => None,
+                    other => Some(SplitDir::from_name(other)
+                        .ok_or(CustomError {
+                            message: format!("Unknown split direction {}", 
+                                name)
+                        })?)
+                };
+                self.cur_wrksp_mut()?.layout_msg(&LayoutMsg::SetSplit(dir));
+            }
+            ActionEnum::RotateSplit => {
+                self.cur_wrksp_mut()?.layout_msg(&LayoutMsg::Rotate);
+                self.update_all_sizes()?;
+            }
+            ActionEnum::FlipSplit => {
+                self.cur_wrksp_mut()?.layout_msg(&LayoutMsg::Flip);
+                self.update_all_sizes()?;
+            }
+            ActionEnum::BalanceTree => {
+                self.cur_wrksp_mut()?.layout_msg(&LayoutMsg::Balance);
+                self.update_all_sizes()?;
+            }
+            ActionEnum::ResizeFocused { dx, dy } => {
+                let cur_wrksp = self.cur_wrksp_mut()?;
+                let focused = cur_wrksp.focused.ok_or(CustomError {
+                    message: "No focused window to resize".to_owned()
+                })?;
+                cur_wrksp.windows.get_mut(&focused)
+                    .ok_or(CustomError {
+                        message: format!("Can't get window {} in cur \
+                            workspace", focused)
+                    })?
+                    .resize(*dx, *dy);
+                self.update_all_sizes()?;
+            }
+            ActionEnum::BalanceWorkspace => {
+                self.cur_wrksp_mut()?.balance();
+                self.update_all_sizes()?;
+            }
+            ActionEnum::DeltaGapsInner(delta) => {
+                let cur_wrksp = self.cur_wrksp_mut()?;
+                cur_wrksp.gaps_inner = cur_wrksp.gaps_inner
+                    .saturating_add_signed(*delta as i32);
+                self.update_all_sizes()?;
+            }
+            ActionEnum::DeltaGapsOuter(delta) => {
+                let cur_wrksp = self.cur_wrksp_mut()?;
+                cur_wrksp.gaps_outer = cur_wrksp.gaps_outer
+                    .saturating_add_signed(*delta as i32);
+                self.update_all_sizes()?;
+            }
+            ActionEnum::ToggleGaps => {
+                let cur_wrksp = self.cur_wrksp_mut()?;
+                cur_wrksp.gaps_on = !cur_wrksp.gaps_on;
+                self.update_all_sizes()?;
+            }
+            ActionEnum::ToggleFloating => {
+                let focused = self.cur_wrksp_mut()?.focused
+                    .ok_or(CustomError {
+                        message: "No focused window to float".to_owned()
+                    })?;
+
+                if self.find_wind_mut(focused).is_some_and(|w| w.floating) {
+                    if let Some(w) = self.find_wind_mut(focused) {
+                        w.floating = false;
+                    }
+                } else {
+                    self.float_wind(focused)?;
+                }
+                self.update_all_sizes()?;
+            }
+            ActionEnum::ToggleFullscreen => {
+                let focused = self.cur_wrksp_mut()?.focused
+                    .ok_or(CustomError {
+                        message: "No focused window".to_owned()
+                    })?;
+                let cur = self.find_wind_mut(focused)
+                    .is_some_and(|w| w.fullscreen);
+                self.set_fullscreen(focused, !cur)?;
+            }
+            ActionEnum::CloseFocused => {
+                let focused = self.cur_wrksp_mut()?.focused
+                    .ok_or(CustomError {
+                        message: "No focused window".to_owned()
+                    })?;
+                self.close_wind(focused)?;
+            }
+            ActionEnum::KillFocused => {
+                let focused = self.cur_wrksp_mut()?.focused
+                    .ok_or(CustomError {
+                        message: "No focused window".to_owned()
+                    })?;
+                self.conn.kill_client(focused)?;
+                self.conn.flush()?;
+            }
+            ActionEnum::FocusUrgent => {
+                if !self.workspaces.values().any(|w| w.urgent) {
+                    return Ok(());
+                }
+                if let Some(id) = self.urgent.first().copied() {
+                    self.activate(id)?;
+                }
+            }
             ActionEnum::CfgReload(_) => {
                 self.reload_cfg()?;
             }
@@ -705,59 +1195,814 @@ impl<C: Connection> YATState<C> {
         Ok(())
     }
     
-    /// Calculate pos and size of new window (posx, posy, sizex, sizey)
-    /// Alg is straightforward: split in vertical bars basically
-    fn calc_cords(&mut self, delta: i16) -> 
-            Result<(u32, u32, u32, u32), Box<dyn std::error::Error>> { 
-        let scr_height = self.screen.height_in_pixels;
-        let scr_width = self.screen.width_in_pixels;
-    
-        let wind_width = self.update_all_sizes(delta)?; 
+    /// Calculate pos and size of every window in cur workspace  
+    /// according to its layout
+    fn calc_cords(&self) -> 
+            Result<Vec<(u32, Rect)>, Box<dyn std::error::Error>> { 
+        let cur_wrksp = self.workspaces
+            .get(&self.cur_scr)
+            .ok_or(CustomError {
+                message: "Can't get cur workspace".to_owned()}
+            )?;
 
-        Ok((
-            (scr_width - wind_width).into(), 
-            0, 
-            wind_width.into(), 
-            scr_height.into()
-        ))
+        let area = self.work_area();
+        let layout = cur_wrksp.layout();
+
+        let (inner, outer) = if cur_wrksp.gaps_on {
+            (cur_wrksp.gaps_inner as i32, cur_wrksp.gaps_outer as i32)
+        } else {
+            (0, 0)
+        };
+        
+        // outer gap is at screen edges, inner one is between tiles:
+        // every tile is shrunk by half of inner gap, odd one is split
+        // unevenly so gaps between tiles are exact
+        let (start, end) = (inner / 2, inner - inner / 2);
+        let tiled = cur_wrksp.tiled();
+        let mut cords: Vec<(u32, Rect)> = layout.arrange(
+            area.shrink_sides(outer - start, outer - end), 
+            &tiled, 
+            cur_wrksp.focused_tiled()
+        ).into_iter()
+            .map(|(id, r)| (id, r.shrink_sides(start, end)))
+            .collect();
+
+        if cords.len() == 1 && self.general.smart_gaps.unwrap_or(false) {
+            cords = layout.arrange(
+                area, 
+                &tiled, 
+                cur_wrksp.focused_tiled()
+            );
+        }
+
+        // x and y include border while width and height don't
+        let bw = self.general.border_width.unwrap_or(1);
+        for (_, r) in cords.iter_mut() {
+            r.w = r.w.saturating_sub(2 * bw).max(1);
+            r.h = r.h.saturating_sub(2 * bw).max(1);
+        }
+        Ok(cords)
     }
 
-    /// Updates all windows sizes and returns new window width except `except`
-    fn update_all_sizes(&mut self, delta: i16) 
-        -> Result<u16, Box<dyn std::error::Error>> {
+    /// Updates all windows sizes and positions in cur workspace. 
+    /// Maps windows placed by layout or floating and unmaps the rest
+    fn update_all_sizes(&mut self) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let mut cords = self.calc_cords()?;
+        let scr = self.screen_rect();
         let cur_wrksp = self.workspaces
             .get_mut(&self.cur_scr)
             .ok_or(CustomError {
                 message: "Can't get cur workspace".to_owned()}
             )?;
-        let ctr = cur_wrksp.windows.len();
 
-        let scr_height = self.screen.height_in_pixels;
-        let scr_width = self.screen.width_in_pixels;
-    
-        let wind_width = if (ctr as i16 + delta) == 0 {
-            scr_width
-        } else {
-            scr_width / (ctr as i16 + delta) as u16
-        };
+        // focused tile goes on top (e.g. in monocle), floating ones above
+        // all tiles, focused floating is the topmost
+        let mut raise: Vec<u32> = cur_wrksp.focused
+            .filter(|f| cords.iter().any(|(c, _)| c == f))
+            .into_iter()
+            .collect();
+        let mut floating: Vec<u32> = Vec::new();
+        for (id, wind) in cur_wrksp.windows.iter() {
+            if wind.floating && let Some(r) = wind.float_rect {
+                cords.push((*id, r));
+                floating.push(*id);
+            }
+        }
+        floating.sort_by_key(|id| cur_wrksp.focused == Some(*id));
+        raise.extend(floating);
+
+        // fullscreen windows take the whole screen without borders 
+        // and are above everything
+        let mut fullscreen = Vec::new();
+        for (id, rect) in cords.iter_mut() {
+            if cur_wrksp.windows.get(id).is_some_and(|w| w.fullscreen) {
+                *rect = scr;
+                fullscreen.push(*id);
+            }
+        }
+        raise.retain(|id| !fullscreen.contains(id));
+        raise.extend(fullscreen.iter());
+
+        // transients stay right above their parents
+        for wind in cur_wrksp.windows.values() {
+            let Some(parent) = wind.transient_for else {
+                continue;
+            };
+            let pos = |id| raise.iter().position(|r| *r == id);
+            if let (Some(i), Some(p)) = (pos(wind.id), pos(parent)) 
+                && i < p {
+                let id = raise.remove(i);
+                raise.insert(p, id);
+            }
+        }
+
+        let hide: Vec<u32> = cur_wrksp.windows.keys()
+            .filter(|id| !cords.iter().any(|(c, _)| c == *id))
+            .copied()
+            .collect();
         
-        for (i, wind) in cur_wrksp.windows.values_mut().enumerate() {
-            let new_x = i * wind_width as usize;
-            
+        let bw = self.general.border_width.unwrap_or(1);
+        let hints_tiled = self.general.size_hints_tiled.unwrap_or(false);
+        for (id, mut rect) in cords {
+            // floating windows get constrained size, tiled ones are 
+            // centered inside their tile
+            if let Some(wind) = cur_wrksp.windows.get(&id) 
+                && !fullscreen.contains(&id)
+                && (wind.floating || hints_tiled)
+                && let Some(hints) = &wind.size_hints {
+                let (w, h) = icccm::constrain(hints, rect.w, rect.h);
+                if wind.floating {
+                    (rect.w, rect.h) = (w, h);
+                } else {
+                    let (w, h) = (w.min(rect.w), h.min(rect.h));
+                    rect = Rect::new(
+                        rect.x + (rect.w - w) as i32 / 2,
+                        rect.y + (rect.h - h) as i32 / 2,
+                        w,
+                        h
+                    );
+                }
+            }
+
             self.conn.configure_window(
-                    wind.id,
+                    id,
                     &ConfigureWindowAux::new()
-                        .x(new_x as i32)
-                        .y(wind.y as i32)
-                        .width(wind_width as u32)
-                        .height(scr_height as u32)
+                        .x(rect.x)
+                        .y(rect.y)
+                        .width(rect.w)
+                        .height(rect.h)
+                        .border_width(if fullscreen.contains(&id) {0} else {bw})
             )?;
 
-            wind.x = new_x as u32;
+            self.conn.map_window(id)?;
+
+            if let Some(wind) = cur_wrksp.windows.get_mut(&id) {
+                wind.x = rect.x as u32;
+                wind.y = rect.y as u32;
+                if !wind.mapped {
+                    wind.mapped = true;
+                    icccm::set_wm_state(&self.conn, &self.atoms, id, 
+                        icccm::WM_STATE_NORMAL)?;
+                }
+            }
+        }
+
+        for id in hide {
+            self.hide_wind(id)?;
+        }
+
+        for id in raise.iter() {
+            self.raise_wind(*id)?;
+        }
+        if !raise.is_empty() {
+            self.update_client_list()?;
         }
         
         self.conn.flush()?;
-        Ok(wind_width)
+        Ok(())
+    }
+
+    /// Sets input focus to window `id`, remembers it in its workspace 
+    /// and publishes it as _NET_ACTIVE_WINDOW. `time` is timestamp of 
+    /// the event that caused focus change. 
+    /// Every focus change goes through it
+    fn focus_wind(&mut self, id: u32, time: u32) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        // ICCCM focus models:
+        // passive - input, locally active - input and WM_TAKE_FOCUS,
+        // globally active - WM_TAKE_FOCUS only, no input - neither
+        let take_atom = self.atoms.WM_TAKE_FOCUS;
+        let (input, take_focus) = self.find_wind_mut(id)
+            .map(|w| (w.input, w.protocols.contains(&take_atom)))
+            .unwrap_or((true, false));
+
+        if input {
+            self.conn.set_input_focus(InputFocus::PARENT, id, time)?;
+        }
+        // ICCCM forbids CurrentTime here
+        if take_focus && time != x11rb::CURRENT_TIME {
+            icccm::send_protocol(
+                &self.conn, 
+                &self.atoms, 
+                id, 
+                self.atoms.WM_TAKE_FOCUS, 
+                time
+            )?;
+        }
+        ewmh::set_active_window(&self.conn, &self.atoms, self.screen.root, 
+            id)?;
+        if self.find_wind_mut(id).is_some_and(|w| w.urgent) {
+            self.set_urgent(id, false)?;
+        }
+
+        if let Some(wrksp) = self.workspaces.values_mut()
+            .find(|w| w.windows.contains_key(&id)) {
+            wrksp.set_focused(id);
+            let idx = wrksp.idx;
+            self.update_borders(idx)?;
+        }
+
+        if self.find_wind_mut(id).is_some_and(|w| w.floating) {
+            self.raise_wind(id)?;
+            self.update_client_list()?;
+        }
+        Ok(())
+    }
+
+    /// Asks window `id` to close if it supports WM_DELETE_WINDOW, 
+    /// kills its client otherwise
+    fn close_wind(&mut self, id: u32) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let delete_atom = self.atoms.WM_DELETE_WINDOW;
+        let delete = self.find_wind_mut(id)
+            .is_some_and(|w| w.protocols.contains(&delete_atom));
+        if delete {
+            icccm::send_protocol(
+                &self.conn, 
+                &self.atoms, 
+                id, 
+                self.atoms.WM_DELETE_WINDOW, 
+                x11rb::CURRENT_TIME
+            )?;
+        } else {
+            self.conn.kill_client(id)?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Starts managing window `id` on workspace `wrksp` (current one 
+    /// if None). Transients go to workspace of their parent instead
+    fn manage_wind(&mut self, id: u32, wrksp: Option<usize>) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let attrs = self.conn.get_window_attributes(id)?.reply()?;
+
+        let kind = ewmh::get_window_kind(
+            &self.conn, 
+            &self.atoms, 
+            id
+        )?;
+        match kind {
+            WindowKind::Dock => {
+                self.manage_dock(id)?;
+                return Ok(());
+            }
+            WindowKind::Unmanaged => {
+                // never focused nor tiled
+                self.conn.map_window(id)?;
+                self.conn.flush()?;
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        // mapped again by client while still managed
+        if self.wrksp_of(id).is_some() {
+            return Ok(());
+        }
+
+        let mut new_win = YATWindow::new(
+            id, 0, 0 // would be updated anyways
+        );
+        // could be already mapped if adopted on startup
+        new_win.mapped = attrs.map_state == MapState::VIEWABLE;
+        if new_win.mapped {
+            icccm::set_wm_state(&self.conn, &self.atoms, id, 
+                icccm::WM_STATE_NORMAL)?;
+        }
+        new_win.input = icccm::accepts_input(
+            icccm::get_wm_hints(&self.conn, id)?.as_ref()
+        );
+        new_win.protocols = icccm::get_protocols(&self.conn, &self.atoms, id)?;
+        new_win.size_hints = icccm::get_size_hints(
+            &self.conn, 
+            id
+        )?;
+        let fixed = new_win.size_hints.as_ref()
+            .is_some_and(icccm::is_fixed);
+
+        // transients go to workspace of their parent
+        let parent = icccm::get_transient_for(&self.conn, id)?
+            .filter(|p| self.wrksp_of(*p).is_some());
+        new_win.transient_for = parent;
+        let wrksp_idx = parent.and_then(|p| self.wrksp_of(p))
+            .or(wrksp)
+            .unwrap_or(self.cur_scr);
+
+        let created = !self.workspaces.contains_key(&wrksp_idx);
+        let wrksp = self.workspaces.entry(wrksp_idx)
+            .or_insert_with(|| Workspace::new(wrksp_idx, &self.general));
+        wrksp.add_wind(id, new_win);
+        let wrksp_len = wrksp.windows.len();
+
+        // if the window is only one or focus_new, focus it
+        let focus = wrksp_idx == self.cur_scr 
+            && (wrksp_len == 1 || self.focus_new);
+        if focus {
+            wrksp.set_focused(id);
+        }
+
+        if created {
+            self.update_desktops()?;
+        }
+        if wrksp_idx != self.cur_scr {
+            self.hide_wind(id)?;
+        }
+
+        if !self.clients.contains(&id) {
+            self.clients.push(id);
+            self.stacking.push(id);
+        }
+        self.update_client_list()?;
+        ewmh::set_wm_desktop(
+            &self.conn, 
+            &self.atoms, 
+            id, 
+            desktop_of(wrksp_idx)
+        )?;
+
+        if kind == WindowKind::Floating || fixed || parent.is_some() {
+            self.float_wind(id)?;
+        }
+        if let Some(p) = parent {
+            self.center_on(id, p)?;
+        }
+
+        let event_mask = EventMask::EXPOSURE
+            | EventMask::STRUCTURE_NOTIFY
+            | EventMask::PROPERTY_CHANGE
+            | EventMask::BUTTON_PRESS    
+            | EventMask::BUTTON_RELEASE  
+            | EventMask::POINTER_MOTION  
+            | EventMask::ENTER_WINDOW   
+            | EventMask::LEAVE_WINDOW
+            | EventMask::FOCUS_CHANGE;   
+
+        self.conn.change_window_attributes(
+            id,
+            &ChangeWindowAttributesAux::default()
+                .event_mask(event_mask)
+        )?;
+
+        // maps the window unless layout hides it
+        self.update_all_sizes()?;
+
+        if focus {
+            self.focus_wind(id, self.time)?;
+        } else {
+            self.update_borders(self.cur_scr)?;
+        }
+
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Manages windows that were mapped before WM started, e.g. on 
+    /// restart. Windows go back to workspaces from their _NET_WM_DESKTOP,
+    /// iconic ones were hidden on other workspaces
+    fn adopt_existing(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let tree = self.conn.query_tree(self.screen.root)?.reply()?;
+        for id in tree.children {
+            // window could be destroyed meanwhile, it shouldn't stop others
+            if let Err(e) = self.adopt_wind(id) {
+                warn!("Failed to adopt window {}: {}", id, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn adopt_wind(&mut self, id: u32) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let attrs = self.conn.get_window_attributes(id)?.reply()?;
+        if attrs.override_redirect {
+            return Ok(());
+        }
+        if attrs.map_state != MapState::VIEWABLE 
+            && icccm::get_wm_state(&self.conn, &self.atoms, id)? 
+                != Some(icccm::WM_STATE_ICONIC) {
+            return Ok(()); // withdrawn
+        }
+
+        // don't trust it to create workspaces
+        let wrksp = ewmh::get_wm_desktop(&self.conn, &self.atoms, id)?
+            .map(|d| d as usize + 1)
+            .filter(|w| self.workspaces.contains_key(w));
+        self.manage_wind(id, wrksp)
+    }
+
+    /// Stops managing window `id` that was destroyed or withdrawn, 
+    /// moves focus if it was focused
+    fn unmanage(&mut self, id: u32) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        if self.wrksp_of(id).is_none() {
+            return Ok(());
+        }
+
+        let was_focused = self.workspaces.get(&self.cur_scr)
+            .is_some_and(|w| w.focused == Some(id));
+        let parent = self.find_wind_mut(id)
+            .and_then(|w| w.transient_for);
+
+        self.rm_any_wind(id);
+        self.update_all_sizes()?;
+
+        let cur_wrksp = self.workspaces
+            .get_mut(&self.cur_scr).ok_or(CustomError {
+            message: "Can't get cur workspace".to_owned()
+         })?;
+
+        // closed dialog gives focus back to its parent
+        if was_focused 
+            && let Some(p) = parent 
+            && cur_wrksp.windows.contains_key(&p) {
+            cur_wrksp.set_focused(p);
+        }
+
+        // if only one window left or the focused one was destroyed,
+        // focus the one workspace falls back to
+        if (cur_wrksp.windows.len() == 1 || was_focused) 
+            && let Some(w) = cur_wrksp.focused
+            .or(cur_wrksp.windows.first().map(|w| *w.0)) {
+
+            self.focus_wind(w, self.time)?;
+            self.conn.flush()?;
+        } else if was_focused {
+            self.unfocus()?;
+            self.conn.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Unmaps managed window `id` remembering that the unmap is ours,
+    /// not a withdrawal
+    fn hide_wind(&mut self, id: u32) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let Some(wind) = self.find_wind_mut(id) else {
+            return Ok(());
+        };
+        if !wind.mapped {
+            return Ok(()); // no UnmapNotify would come
+        }
+        wind.mapped = false;
+        wind.ignore_unmaps += 1;
+
+        self.conn.unmap_window(id)?;
+        icccm::set_wm_state(&self.conn, &self.atoms, id, 
+            icccm::WM_STATE_ICONIC)?;
+        Ok(())
+    }
+
+    /// Maps dock window `id` without managing it and reserves 
+    /// space it asks for
+    fn manage_dock(&mut self, id: u32) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let strut = self.dock_strut(id)?;
+        self.docks.insert(id, strut);
+
+        self.conn.change_window_attributes(
+            id,
+            &ChangeWindowAttributesAux::default().event_mask(
+                EventMask::STRUCTURE_NOTIFY | EventMask::PROPERTY_CHANGE
+            )
+        )?;
+        self.conn.map_window(id)?;
+        self.update_workarea()?;
+        self.update_all_sizes()?;
+        Ok(())
+    }
+
+    /// Returns space reserved by dock `id` from its strut. Docks without
+    /// one reserve the screen edge they're placed at
+    fn dock_strut(&self, id: u32) 
+        -> Result<[u32; 4], Box<dyn std::error::Error>> {
+        if let Some(strut) = ewmh::get_strut(&self.conn, &self.atoms, id)? {
+            return Ok(strut);
+        }
+
+        let geom = self.conn.get_geometry(id)?.reply()?;
+        let (x, y) = (geom.x as i32, geom.y as i32);
+        let (w, h) = (geom.width as i32, geom.height as i32);
+        let scr_w = self.screen.width_in_pixels as i32;
+        let scr_h = self.screen.height_in_pixels as i32;
+
+        let mut strut = [0u32; 4];
+        if w >= h {
+            if y + h / 2 < scr_h / 2 {
+                strut[2] = (y + h).max(0) as u32;
+            } else {
+                strut[3] = (scr_h - y).max(0) as u32;
+            }
+        } else if x + w / 2 < scr_w / 2 {
+            strut[0] = (x + w).max(0) as u32;
+        } else {
+            strut[1] = (scr_w - x).max(0) as u32;
+        }
+        Ok(strut)
+    }
+
+    /// Drops focus to root when there is nothing to focus
+    fn unfocus(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn.set_input_focus(
+            InputFocus::POINTER_ROOT, 
+            self.screen.root, 
+            self.time
+        )?;
+        ewmh::set_active_window(&self.conn, &self.atoms, self.screen.root, 
+            x11rb::NONE)?;
+        Ok(())
+    }
+
+    /// Focuses window `id` switching to its workspace if needed
+    fn activate(&mut self, id: u32) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let idx = self.wrksp_of(id).ok_or(CustomError {
+            message: format!("Window {} isn't managed", id)
+        })?;
+
+        // layout could show only focused window (e.g. monocle)
+        if let Some(wrksp) = self.workspaces.get_mut(&idx) {
+            wrksp.set_focused(id);
+        }
+
+        if idx != self.cur_scr {
+            self.change_workspace(idx)?; // focuses it as well
+        } else {
+            self.update_all_sizes()?;
+            self.focus_wind(id, self.time)?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Starts moving (`button` 1) or resizing (`button` 3) window `id` 
+    /// by mouse. Tiled window becomes floating unless `drag_tiled` 
+    /// is "swap"
+    fn start_drag(&mut self, id: u32, button: u8, x: i16, y: i16) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        self.focus_wind(id, self.time)?;
+
+        let geom = self.conn.get_geometry(id)?.reply()?;
+        let orig = Rect::new(
+            geom.x.into(), 
+            geom.y.into(), 
+            geom.width.into(), 
+            geom.height.into()
+        );
+
+        let floating = self.find_wind_mut(id).is_some_and(|w| w.floating);
+        let tiled = !floating && self.general.drag_swaps();
+        if !floating && !tiled {
+            // floats right where it is
+            if let Some(wind) = self.find_wind_mut(id) {
+                wind.float_rect = Some(orig);
+            }
+            self.float_wind(id)?;
+            self.update_all_sizes()?;
+        }
+
+        self.conn.grab_pointer(
+            false,
+            self.screen.root,
+            EventMask::POINTER_MOTION | EventMask::BUTTON_RELEASE,
+            GrabMode::ASYNC,
+            GrabMode::ASYNC,
+            x11rb::NONE,
+            x11rb::NONE,
+            x11rb::CURRENT_TIME
+        )?.reply()?;
+
+        self.drag = Some(Drag {
+            wind: id,
+            button,
+            start_x: x,
+            start_y: y,
+            orig,
+            tiled,
+        });
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Finishes drag of tiled window: moving swaps it with the window 
+    /// `target` it's dropped onto, resizing changes its size weights
+    fn end_tiled_drag(&mut self, drag: &Drag, target: u32, x: i16, y: i16) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let cur_wrksp = self.cur_wrksp_mut()?;
+        if !cur_wrksp.windows.contains_key(&drag.wind) {
+            return Ok(());
+        }
+
+        if drag.button == 1 {
+            if target == drag.wind || !cur_wrksp.windows.contains_key(&target) {
+                return Ok(());
+            }
+            cur_wrksp.swap_winds(drag.wind, target);
+        } else if let Some(wind) = cur_wrksp.windows.get_mut(&drag.wind) {
+            let dx = (x - drag.start_x) as isize * 100 / drag.orig.w as isize;
+            let dy = (y - drag.start_y) as isize * 100 / drag.orig.h as isize;
+            wind.resize(dx, dy);
+        }
+
+        self.update_all_sizes()
+    }
+
+    /// Makes window `id` floating. It gets remembered floating geometry 
+    /// or is centered on the screen
+    fn float_wind(&mut self, id: u32) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let geom = self.conn.get_geometry(id)?.reply()?;
+        let centered = self.centered(geom.width.into(), geom.height.into());
+
+        let wind = self.find_wind_mut(id).ok_or(CustomError {
+            message: format!("Window {} isn't managed", id)
+        })?;
+        wind.floating = true;
+        wind.float_rect.get_or_insert(centered);
+        Ok(())
+    }
+
+    /// Places floating window `id` at center of window `parent`
+    fn center_on(&mut self, id: u32, parent: u32) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let pgeom = self.conn.get_geometry(parent)?.reply()?;
+        let wind = self.find_wind_mut(id).ok_or(CustomError {
+            message: format!("Window {} isn't managed", id)
+        })?;
+
+        if let Some(r) = wind.float_rect.as_mut() {
+            r.x = pgeom.x as i32 + (pgeom.width as i32 - r.w as i32) / 2;
+            r.y = pgeom.y as i32 + (pgeom.height as i32 - r.h as i32) / 2;
+        }
+        Ok(())
+    }
+
+    /// Sets or unsets fullscreen state of window `id`, 
+    /// keeps _NET_WM_STATE in sync
+    fn set_fullscreen(&mut self, id: u32, on: bool) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let wind = self.find_wind_mut(id).ok_or(CustomError {
+            message: format!("Window {} isn't managed", id)
+        })?;
+        wind.fullscreen = on;
+        self.update_wm_state(id)?;
+
+        self.update_all_sizes()
+    }
+
+    /// Sets or unsets urgency of window `id`, highlights its border
+    /// and keeps _NET_WM_STATE in sync
+    fn set_urgent(&mut self, id: u32, on: bool) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        // focused window on cur workspace has user's attention already
+        let focused = self.workspaces.get(&self.cur_scr)
+            .is_some_and(|w| w.focused == Some(id));
+        let on = on && !focused;
+
+        let wind = self.find_wind_mut(id).ok_or(CustomError {
+            message: format!("Window {} isn't managed", id)
+        })?;
+        wind.urgent = on;
+
+        self.urgent.retain(|u| *u != id);
+        if on {
+            self.urgent.push(id);
+        }
+        self.update_wm_state(id)?;
+
+        if let Some(idx) = self.wrksp_of(id) 
+            && let Some(wrksp) = self.workspaces.get_mut(&idx) {
+            wrksp.update_urgent();
+            self.update_borders(idx)?;
+        }
+        Ok(())
+    }
+
+    /// Publishes _NET_WM_STATE of window `id` from its flags
+    fn update_wm_state(&mut self, id: u32) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let Some(wind) = self.find_wind_mut(id) else {
+            return Ok(());
+        };
+        let (fullscreen, urgent) = (wind.fullscreen, wind.urgent);
+
+        ewmh::set_wm_state(&self.conn, &self.atoms, id, &[
+            (self.atoms._NET_WM_STATE_FULLSCREEN, fullscreen),
+            (self.atoms._NET_WM_STATE_DEMANDS_ATTENTION, urgent),
+        ])?;
+        Ok(())
+    }
+
+    fn screen_rect(&self) -> Rect {
+        Rect::new(
+            0, 
+            0, 
+            self.screen.width_in_pixels.into(), 
+            self.screen.height_in_pixels.into()
+        )
+    }
+
+    /// Screen area left for tiling after space reserved by docks
+    fn work_area(&self) -> Rect {
+        let scr = self.screen_rect();
+        let mut strut = [0u32; 4];
+        for s in self.docks.values() {
+            for (r, v) in strut.iter_mut().zip(s) {
+                *r = (*r).max(*v);
+            }
+        }
+        let [left, right, top, bottom] = strut;
+
+        Rect::new(
+            left as i32,
+            top as i32,
+            scr.w.saturating_sub(left + right).max(1),
+            scr.h.saturating_sub(top + bottom).max(1)
+        )
+    }
+
+    /// Returns rect of size `w`x`h` centered in the work area, 
+    /// so docks don't cover it
+    fn centered(&self, w: u32, h: u32) -> Rect {
+        let area = self.work_area();
+        let (w, h) = (w.min(area.w), h.min(area.h));
+
+        Rect::new(
+            area.x + ((area.w - w) / 2) as i32,
+            area.y + ((area.h - h) / 2) as i32,
+            w,
+            h
+        )
+    }
+
+    fn find_wind_mut(&mut self, id: u32) -> Option<&mut YATWindow> {
+        self.workspaces.values_mut()
+            .find_map(|w| w.windows.get_mut(&id))
+    }
+
+    /// Updates border colors of all windows in workspace `wrksp_idx`
+    fn update_borders(&self, wrksp_idx: usize) 
+        -> Result<(), Box<dyn std::error::Error>> {
+        let Some(wrksp) = self.workspaces.get(&wrksp_idx) else {
+            return Ok(());
+        };
+
+        for (id, wind) in wrksp.windows.iter() {
+            let color = if wrksp.focused == Some(*id) {
+                self.general.border_focused()
+            } else if wind.urgent {
+                self.general.border_urgent()
+            } else {
+                self.general.border_unfocused()
+            };
+
+            self.conn.change_window_attributes(
+                *id,
+                &ChangeWindowAttributesAux::new().border_pixel(color)
+            )?;
+        }
+        Ok(())
+    }
+
+    fn cur_wrksp_mut(&mut self) 
+        -> Result<&mut Workspace, Box<dyn std::error::Error>> {
+        self.workspaces
+            .get_mut(&self.cur_scr)
+            .ok_or(Box::new(CustomError {
+                message: format!("Can't get cur workspace {}", self.cur_scr)
+            }))
+    }
+
+    /// Grabs mainmod+right button for resizing by drag, dropping grabs 
+    /// with the previous mainmod. Grabs match modifiers exactly, so 
+    /// also with Caps Lock and Num Lock (Mod2)
+    fn grab_resize(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn.ungrab_button(ButtonIndex::M3, self.screen.root, 
+            ModMask::ANY)?;
+        let Some(mainmod) = self.general.mainmod_mask() else {
+            return Ok(());
+        };
+
+        let locks = [
+            ModMask::default(),
+            ModMask::LOCK,
+            ModMask::M2,
+            ModMask::LOCK | ModMask::M2,
+        ];
+        for lock in locks {
+            self.conn.grab_button(
+                true,
+                self.screen.root,
+                EventMask::BUTTON_PRESS | EventMask::BUTTON_RELEASE,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                x11rb::NONE,
+                x11rb::NONE,
+                ButtonIndex::M3,
+                mainmod | lock,
+            )?;
+        }
+        Ok(())
     }
 
     fn reload_cfg(&mut self) -> 
@@ -765,6 +2010,7 @@ impl<C: Connection> YATState<C> {
 
         let new_cfg = Config::from_def_dir();
         self.focus_new = new_cfg.general.focus_new.unwrap_or(true);
+        self.general = new_cfg.general.clone();
         self.inpct.shell = new_cfg.general.sh
             .clone()
             .unwrap_or("sh".to_owned());
@@ -784,25 +2030,87 @@ impl<C: Connection> YATState<C> {
         )?;
 
         self.reg_scuts(&new_cfg);
+        self.grab_resize()?;
         self.conn.flush()?;
         
         Ok(())
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct YATWindow {
     pub id: u32,
     pub x: u32,
     pub y: u32,
+    pub weight_w: f32, // relative tile width, 1.0 by default
+    pub weight_h: f32, // relative tile height
+    pub urgent: bool,
+    pub floating: bool,
+    pub float_rect: Option<Rect>, // last floating geometry
+    pub fullscreen: bool,
+    pub size_hints: Option<WmSizeHints>, // WM_NORMAL_HINTS
+    pub transient_for: Option<u32>, // parent of dialog
+    pub mapped: bool,
+    pub ignore_unmaps: u32, // unmaps done by WM, not by client
+    pub input: bool, // WM_HINTS input, whether WM may focus it
+    pub protocols: Vec<u32>, // WM_PROTOCOLS
 }
 
 impl YATWindow {
     pub fn new(id: u32, x: u32, y: u32) -> YATWindow {
-        YATWindow { id, x, y }
+        YATWindow { 
+            id, 
+            x, 
+            y, 
+            weight_w: 1.0, 
+            weight_h: 1.0, 
+            urgent: false,
+            floating: false,
+            float_rect: None,
+            fullscreen: false,
+            size_hints: None,
+            transient_for: None,
+            mapped: false,
+            ignore_unmaps: 0,
+            input: true,
+            protocols: Vec::new(),
+        }
+    }
+
+    /// Changes size weights by `dx`, `dy` percents
+    pub fn resize(&mut self, dx: isize, dy: isize) {
+        self.weight_w = (self.weight_w + dx as f32 / 100.0)
+            .clamp(WEIGHT_MIN, WEIGHT_MAX);
+        self.weight_h = (self.weight_h + dy as f32 / 100.0)
+            .clamp(WEIGHT_MIN, WEIGHT_MAX);
     }
 }
 
+const WEIGHT_MIN: f32 = 0.1;
+const WEIGHT_MAX: f32 = 10.0;
+
+/// Server timestamp carried by `ev`, if any
+fn event_time(ev: &Event) -> Option<u32> {
+    let time = match ev {
+        Event::KeyPress(e) => e.time,
+        Event::KeyRelease(e) => e.time,
+        Event::ButtonPress(e) => e.time,
+        Event::ButtonRelease(e) => e.time,
+        Event::MotionNotify(e) => e.time,
+        Event::EnterNotify(e) => e.time,
+        Event::LeaveNotify(e) => e.time,
+        Event::PropertyNotify(e) => e.time,
+        _ => return None,
+    };
+    Some(time)
+}
+
+/// EWMH desktop number of workspace `idx`. Workspace 0 can only be 
+/// reached with SwitchWorkspace(0) and shares desktop 0
+fn desktop_of(idx: usize) -> u32 {
+    idx.saturating_sub(1) as u32
+}
+
 pub fn get_homedpath(append: &str, cleanup: bool) -> Result<String, ()> {
     if let Some(path) = std::env::home_dir() {
         let res = format!("{}", path
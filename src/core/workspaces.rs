@@ -1,28 +1,205 @@
 use indexmap::IndexMap;
+use log::error;
 
-use crate::core::YATWindow;
+use crate::core::{YATWindow, cfgread::General, layouts::{self, Layout, LayoutMsg}};
 
 
 #[derive(Debug)]
 pub struct Workspace {
     pub idx: usize, 
     pub windows: IndexMap<u32, YATWindow>,
+    pub focused: Option<u32>, // last focused window
+    last_tiled: Option<u32>, // last focused not floating window
+    pub urgent: bool, // any window is urgent
+    layouts: Vec<Box<dyn Layout>>,
+    cur_layout: usize, // index in `layouts`
+    pub gaps_inner: u32,
+    pub gaps_outer: u32,
+    pub gaps_on: bool,
 }
 
 impl Workspace {
-    pub fn new(idx: usize) -> Workspace {
-        Workspace {
+    pub fn new(idx: usize, cfg: &General) -> Workspace {
+        let gaps = cfg.wrksp_gaps.as_ref().and_then(|m| m.get(&idx));
+
+        let mut wrksp = Workspace {
             idx: idx, 
             windows: IndexMap::new(), 
+            focused: None,
+            last_tiled: None,
+            urgent: false,
+            layouts: layouts::builtin_layouts(cfg),
+            cur_layout: 0,
+            gaps_inner: gaps.and_then(|g| g.inner)
+                .or(cfg.gaps_inner)
+                .unwrap_or(0),
+            gaps_outer: gaps.and_then(|g| g.outer)
+                .or(cfg.gaps_outer)
+                .unwrap_or(0),
+            gaps_on: true,
+        };
+
+        let def = cfg.wrksp_layouts.as_ref()
+            .and_then(|m| m.get(&idx))
+            .or(cfg.def_layout.as_ref());
+        if let Some(name) = def && !wrksp.set_layout(name) {
+            error!("Unknown layout {} for workspace {}", name, idx);
         }
+
+        wrksp
     }
 
     pub fn add_wind(&mut self, idx: u32, wind: YATWindow) {
+        for l in self.layouts.iter_mut() {
+            l.add_wind(idx, self.focused);
+        }
         self.windows.insert(idx, wind);
+        self.update_urgent();
     }
 
     // remove window
     pub fn rm_wind(&mut self, idx: u32) -> Option<YATWindow> {
-        self.windows.remove(&idx)
+        let pos = self.windows.get_index_of(&idx)?;
+        let res = self.windows.shift_remove_index(pos).map(|(_, w)| w);
+        for l in self.layouts.iter_mut() {
+            l.rm_wind(idx);
+        }
+
+        // focus falls back to the previous window
+        if self.focused == Some(idx) {
+            self.focused = self.windows
+                .get_index(pos.saturating_sub(1))
+                .map(|(id, _)| *id);
+        }
+        if self.last_tiled == Some(idx) {
+            self.last_tiled = None;
+        }
+        self.update_urgent();
+        res
+    }
+
+    /// Focuses window `id`, remembering the previous one if it's tiled
+    pub fn set_focused(&mut self, id: u32) {
+        if let Some(prev) = self.focused.filter(|f| self.is_tiled(*f)) {
+            self.last_tiled = Some(prev);
+        }
+        self.focused = Some(id);
+    }
+
+    /// Window that layouts showing only one (e.g. monocle) should show. 
+    /// Floating focused window keeps its parent or last focused tile
+    pub fn focused_tiled(&self) -> Option<u32> {
+        let focused = self.focused?;
+        let parent = self.windows.get(&focused)
+            .and_then(|w| w.transient_for);
+        [Some(focused), parent, self.last_tiled].into_iter()
+            .flatten()
+            .find(|id| self.is_tiled(*id))
+    }
+
+    fn is_tiled(&self, id: u32) -> bool {
+        self.windows.get(&id).is_some_and(|w| !w.floating)
+    }
+
+    /// Recomputes `urgent` from windows
+    pub fn update_urgent(&mut self) {
+        self.urgent = self.windows.values().any(|w| w.urgent);
+    }
+
+    /// Swaps places of windows `a` and `b`
+    pub fn swap_winds(&mut self, a: u32, b: u32) {
+        if let (Some(ia), Some(ib)) = (self.windows.get_index_of(&a), 
+            self.windows.get_index_of(&b)) {
+            self.windows.swap_indices(ia, ib);
+            for l in self.layouts.iter_mut() {
+                l.message(&LayoutMsg::Swap(a, b), self.focused);
+            }
+        }
+    }
+
+    /// Returns windows placed by layout, i.e. not floating ones
+    pub fn tiled(&self) -> IndexMap<u32, YATWindow> {
+        self.windows.iter()
+            .filter(|(_, w)| !w.floating)
+            .map(|(id, w)| (*id, w.clone()))
+            .collect()
+    }
+
+    pub fn layout(&self) -> &dyn Layout {
+        self.layouts[self.cur_layout].as_ref()
+    }
+
+    /// Sets layout by name, returns false if there is no such layout
+    pub fn set_layout(&mut self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        match self.layouts.iter().position(|l| l.name() == name) {
+            Some(i) => {
+                self.cur_layout = i;
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Switches to `delta`-th next layout, wraps around
+    pub fn cycle_layout(&mut self, delta: isize) {
+        let len = self.layouts.len() as isize;
+        self.cur_layout = (self.cur_layout as isize + delta)
+            .rem_euclid(len) as usize;
+    }
+
+    /// Resets windows size weights and balances layout
+    pub fn balance(&mut self) {
+        for wind in self.windows.values_mut() {
+            wind.weight_w = 1.0;
+            wind.weight_h = 1.0;
+        }
+        self.layout_msg(&LayoutMsg::Balance);
+    }
+
+    pub fn layout_msg(&mut self, msg: &LayoutMsg) {
+        self.layouts[self.cur_layout].message(msg, self.focused);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(ids: &[u32]) -> Workspace {
+        let cfg: General = toml::from_str("mainmod = \"super\"").unwrap();
+        let mut wrksp = Workspace::new(1, &cfg);
+        for id in ids {
+            wrksp.add_wind(*id, YATWindow::new(*id, 0, 0));
+        }
+        wrksp
+    }
+
+    fn float(wrksp: &mut Workspace, id: u32, parent: Option<u32>) {
+        let wind = wrksp.windows.get_mut(&id).unwrap();
+        wind.floating = true;
+        wind.transient_for = parent;
+    }
+
+    #[test]
+    fn focused_tiled_dialog_keeps_parent() {
+        let mut wrksp = workspace(&[1, 2, 3]);
+        wrksp.set_focused(1);
+        wrksp.set_focused(3);
+        wrksp.add_wind(4, YATWindow::new(4, 0, 0));
+        wrksp.set_focused(4);
+        float(&mut wrksp, 4, Some(2));
+        assert_eq!(wrksp.focused_tiled(), Some(2));
+
+        // without parent the previous tile stays
+        float(&mut wrksp, 4, None);
+        assert_eq!(wrksp.focused_tiled(), Some(3));
+
+        wrksp.rm_wind(3);
+        wrksp.set_focused(4);
+        assert_eq!(wrksp.focused_tiled(), None);
+
+        wrksp.set_focused(1);
+        assert_eq!(wrksp.focused_tiled(), Some(1));
     }
 }
@@ -9,8 +9,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
+    let replace = args.iter().any(|a| a == "--replace");
+
     let mut wm = WM::new();
-    wm.run()?;
+    if let Err(e) = wm.run(replace) {
+        log::error!("{}", e);
+        eprintln!("{}: {}", env!("CARGO_PKG_NAME"), e);
+        std::process::exit(1);
+    }
 
     Ok(())
 }
@@ -0,0 +1,220 @@
+//! Runs yatwm on a virtual X server and checks that override-redirect
+//! windows (menus, tooltips, drag icons) are never managed.
+//! Needs Xvfb, run with `cargo test -- --ignored`
+
+use std::{path::PathBuf, process::{Child, Command, Stdio}, thread, time::{Duration, Instant}};
+
+use x11rb::{COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, connection::Connection, protocol::xproto::{AtomEnum, ChangeWindowAttributesAux, ConnectionExt, CreateWindowAux, EventMask, MapState, WindowClass}, protocol::Event, rust_connection::RustConnection};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const POLL: Duration = Duration::from_millis(20);
+// time given to WM to handle events it has no reply for
+const SETTLE: Duration = Duration::from_millis(300);
+
+type Geom = (i16, i16, u16, u16);
+
+/// Kills spawned processes and removes temp home when test ends,
+/// even on panic
+struct Session {
+    procs: Vec<Child>,
+    home: PathBuf,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        for p in self.procs.iter_mut().rev() {
+            let _ = p.kill();
+            let _ = p.wait();
+        }
+        let _ = std::fs::remove_dir_all(&self.home);
+    }
+}
+
+/// Polls `cond` until it's true or TIMEOUT passes
+fn wait_until(mut cond: impl FnMut() -> bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < TIMEOUT {
+        if cond() {
+            return true;
+        }
+        thread::sleep(POLL);
+    }
+    false
+}
+
+/// Starts Xvfb on a free display, returns connection to it
+fn start_x() -> (Session, RustConnection, usize, String) {
+    let pid = std::process::id();
+    let display = (100 + pid % 900..1000)
+        .find(|n| !PathBuf::from(format!("/tmp/.X11-unix/X{}", n)).exists())
+        .expect("No free display");
+    let display = format!(":{}", display);
+
+    // log file is created in ~/.local/state
+    let home = std::env::temp_dir().join(format!("yatwm-test-{}", pid));
+    std::fs::create_dir_all(home.join(".local/state")).unwrap();
+    let mut session = Session { procs: Vec::new(), home };
+
+    session.procs.push(Command::new("Xvfb")
+        .args([display.as_str(), "-screen", "0", "800x600x24",
+            "-nolisten", "tcp"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to start Xvfb, is it installed?"));
+
+    let mut conn = None;
+    assert!(wait_until(|| {
+        conn = x11rb::connect(Some(&display)).ok();
+        conn.is_some()
+    }), "Xvfb didn't start");
+    let (conn, scr_num) = conn.unwrap();
+    (session, conn, scr_num, display)
+}
+
+/// Starts yatwm on `display` and waits until it's ready
+fn start_wm(session: &mut Session, conn: &RustConnection, scr_num: usize, 
+    display: &str) {
+    session.procs.push(Command::new(env!("CARGO_BIN_EXE_yatwm"))
+        .env("DISPLAY", display)
+        .env("HOME", &session.home)
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("Failed to start yatwm"));
+
+    let root = conn.setup().roots[scr_num].root;
+    let check = atom(conn, "_NET_SUPPORTING_WM_CHECK");
+    assert!(wait_until(|| !windows_prop(conn, root, check).is_empty()),
+        "yatwm didn't start");
+}
+
+fn atom(conn: &RustConnection, name: &str) -> u32 {
+    conn.intern_atom(false, name.as_bytes()).unwrap().reply().unwrap().atom
+}
+
+fn windows_prop(conn: &RustConnection, wind: u32, prop: u32) -> Vec<u32> {
+    conn.get_property(false, wind, prop, AtomEnum::WINDOW, 0, u32::MAX)
+        .unwrap()
+        .reply()
+        .unwrap()
+        .value32()
+        .map(|v| v.collect())
+        .unwrap_or_default()
+}
+
+fn create_window(conn: &RustConnection, root: u32, geom: Geom,
+    override_redirect: bool) -> u32 {
+    let id = conn.generate_id().unwrap();
+    conn.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        id,
+        root,
+        geom.0, geom.1, geom.2, geom.3, 0,
+        WindowClass::INPUT_OUTPUT,
+        COPY_FROM_PARENT,
+        &CreateWindowAux::new().override_redirect(u32::from(override_redirect))
+    ).unwrap();
+    conn.map_window(id).unwrap();
+    conn.flush().unwrap();
+    id
+}
+
+fn geometry(conn: &RustConnection, id: u32) -> Geom {
+    let g = conn.get_geometry(id).unwrap().reply().unwrap();
+    (g.x, g.y, g.width, g.height)
+}
+
+fn viewable(conn: &RustConnection, id: u32) -> bool {
+    conn.get_window_attributes(id).unwrap().reply().unwrap()
+        .map_state == MapState::VIEWABLE
+}
+
+/// Returns windows from `ids` that got ConfigureNotify since last call
+fn reconfigured(conn: &RustConnection, ids: &[u32]) -> Vec<u32> {
+    let mut res = Vec::new();
+    while let Some(ev) = conn.poll_for_event().unwrap() {
+        if let Event::ConfigureNotify(e) = ev
+            && ids.contains(&e.window) {
+            res.push(e.window);
+        }
+    }
+    res
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn popup_existing_on_start_is_not_adopted() {
+    let (mut session, conn, scr_num, display) = start_x();
+    let root = conn.setup().roots[scr_num].root;
+
+    // there's no MapRequest for these, yatwm finds them in the tree
+    let initial = (10, 10, 100, 100);
+    let popup_geom = (200, 200, 50, 50);
+    let a = create_window(&conn, root, initial, false);
+    let popup = create_window(&conn, root, popup_geom, true);
+    assert!(wait_until(|| viewable(&conn, a) && viewable(&conn, popup)));
+
+    start_wm(&mut session, &conn, scr_num, &display);
+    let clients_atom = atom(&conn, "_NET_CLIENT_LIST");
+    assert!(wait_until(|| windows_prop(&conn, root, clients_atom)
+        .contains(&a)), "existing window wasn't adopted");
+    thread::sleep(SETTLE);
+
+    assert!(!windows_prop(&conn, root, clients_atom).contains(&popup));
+    assert_eq!(geometry(&conn, popup), popup_geom);
+    assert!(viewable(&conn, popup));
+    let wm_desktop = atom(&conn, "_NET_WM_DESKTOP");
+    let prop = conn.get_property(false, popup, wm_desktop, AtomEnum::ANY, 
+        0, 1).unwrap().reply().unwrap();
+    assert_eq!(prop.type_, u32::from(AtomEnum::NONE), 
+        "popup got a desktop");
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn popup_does_not_change_tiles() {
+    let (mut session, conn, scr_num, display) = start_x();
+    start_wm(&mut session, &conn, scr_num, &display);
+    let root = conn.setup().roots[scr_num].root;
+    let initial = (10, 10, 100, 100);
+
+    let a = create_window(&conn, root, initial, false);
+    let b = create_window(&conn, root, initial, false);
+    assert!(wait_until(|| viewable(&conn, a) && viewable(&conn, b)
+        && geometry(&conn, b) != initial), "windows weren't tiled");
+    thread::sleep(SETTLE);
+    let tiles = [geometry(&conn, a), geometry(&conn, b)];
+    assert_ne!(tiles[0], tiles[1]);
+
+    // WM configuring tiles, even to the same geometry, shows up here
+    let aux = ChangeWindowAttributesAux::new()
+        .event_mask(EventMask::STRUCTURE_NOTIFY);
+    for id in [a, b] {
+        conn.change_window_attributes(id, &aux).unwrap();
+    }
+    // round trip, so the mask is set before anything below
+    conn.get_input_focus().unwrap().reply().unwrap();
+    reconfigured(&conn, &[a, b]);
+
+    let popup_geom = (200, 200, 50, 50);
+    let popup = create_window(&conn, root, popup_geom, true);
+    thread::sleep(SETTLE);
+
+    assert_eq!(geometry(&conn, popup), popup_geom);
+    assert!(viewable(&conn, popup));
+    let clients = windows_prop(&conn, root, atom(&conn, "_NET_CLIENT_LIST"));
+    assert!(clients.contains(&a) && clients.contains(&b));
+    assert!(!clients.contains(&popup));
+
+    // menu closing and reopening, then going away for good
+    conn.unmap_window(popup).unwrap();
+    conn.map_window(popup).unwrap();
+    conn.unmap_window(popup).unwrap();
+    conn.destroy_window(popup).unwrap();
+    conn.flush().unwrap();
+    thread::sleep(SETTLE);
+
+    assert_eq!(reconfigured(&conn, &[a, b]), Vec::<u32>::new(),
+        "popup map, unmap or destroy relayouted tiles");
+    assert_eq!([geometry(&conn, a), geometry(&conn, b)], tiles);
+}